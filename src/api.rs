@@ -0,0 +1,690 @@
+use chrono::prelude::*;
+use chrono_tz::Tz;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use std::path::PathBuf;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::cache::ResponseCache;
+use crate::config::SessionInfo;
+use crate::error::ConnecteamError;
+use crate::json_ext::parse_response;
+use crate::models::{ContentStructure, Response};
+
+#[derive(Serialize, Deserialize)]
+struct TimesheetParams {
+    #[serde(rename = "startDate")]
+    start_date: String,
+
+    #[serde(rename = "endDate")]
+    end_date: String,
+
+    #[serde(rename = "objectId")]
+    object_id: u64,
+
+    #[serde(rename = "userId", skip_serializing_if = "Option::is_none")]
+    user_id: Option<u64>,
+
+    #[serde(rename = "defaultTimezone")]
+    default_timezone: String,
+
+    #[serde(rename = "_spirit")]
+    _spirit: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AddShiftParams {
+    #[serde(rename = "objectId")]
+    object_id: u64,
+
+    #[serde(rename = "startTimestamp")]
+    start_timestamp: i64,
+
+    #[serde(rename = "endTimestamp")]
+    end_timestamp: i64,
+
+    #[serde(rename = "tagId")]
+    tag_id: u64,
+
+    #[serde(rename = "subTagId", skip_serializing_if = "Option::is_none")]
+    sub_tag_id: Option<u64>,
+
+    #[serde(rename = "employeeNotes")]
+    employee_notes: String,
+
+    #[serde(rename = "defaultTimezone")]
+    default_timezone: String,
+
+    #[serde(rename = "_spirit")]
+    _spirit: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EditShiftParams {
+    #[serde(rename = "objectId")]
+    object_id: u64,
+
+    #[serde(rename = "shiftId")]
+    shift_id: String,
+
+    #[serde(rename = "startTimestamp")]
+    start_timestamp: i64,
+
+    #[serde(rename = "endTimestamp")]
+    end_timestamp: i64,
+
+    #[serde(rename = "tagId")]
+    tag_id: u64,
+
+    #[serde(rename = "subTagId", skip_serializing_if = "Option::is_none")]
+    sub_tag_id: Option<u64>,
+
+    #[serde(rename = "employeeNotes")]
+    employee_notes: String,
+
+    #[serde(rename = "defaultTimezone")]
+    default_timezone: String,
+
+    #[serde(rename = "_spirit")]
+    _spirit: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DeleteShiftParams {
+    #[serde(rename = "objectId")]
+    object_id: u64,
+
+    #[serde(rename = "shiftId")]
+    shift_id: String,
+
+    #[serde(rename = "_spirit")]
+    _spirit: String,
+}
+
+pub struct NewShift {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub tag_id: u64,
+    pub sub_tag_id: Option<u64>,
+    pub note: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PunchInParams {
+    #[serde(rename = "objectId")]
+    object_id: u64,
+
+    #[serde(rename = "timestamp")]
+    timestamp: i64,
+
+    #[serde(rename = "tagId")]
+    tag_id: u64,
+
+    #[serde(rename = "subTagId", skip_serializing_if = "Option::is_none")]
+    sub_tag_id: Option<u64>,
+
+    #[serde(rename = "defaultTimezone")]
+    default_timezone: String,
+
+    #[serde(rename = "_spirit")]
+    _spirit: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PunchOutParams {
+    #[serde(rename = "objectId")]
+    object_id: u64,
+
+    #[serde(rename = "timestamp")]
+    timestamp: i64,
+
+    #[serde(rename = "employeeNotes")]
+    employee_notes: String,
+
+    #[serde(rename = "defaultTimezone")]
+    default_timezone: String,
+
+    #[serde(rename = "_spirit")]
+    _spirit: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ShiftNoteParams {
+    #[serde(rename = "objectId")]
+    object_id: u64,
+
+    #[serde(rename = "employeeNotes")]
+    employee_notes: String,
+
+    #[serde(rename = "defaultTimezone")]
+    default_timezone: String,
+
+    #[serde(rename = "_spirit")]
+    _spirit: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LoginCodeParams {
+    #[serde(rename = "email", skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+
+    #[serde(rename = "phoneNumber", skip_serializing_if = "Option::is_none")]
+    phone_number: Option<String>,
+
+    #[serde(rename = "code", skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+}
+
+/// Account a verification code is sent to when logging in without pasted cookies.
+pub enum LoginIdentity {
+    Email(String),
+    Phone(String),
+}
+
+impl LoginIdentity {
+    fn params(&self, code: Option<&str>) -> LoginCodeParams {
+        let (email, phone_number) = match self {
+            LoginIdentity::Email(email) => (Some(email.clone()), None),
+            LoginIdentity::Phone(phone) => (None, Some(phone.clone())),
+        };
+        return LoginCodeParams { email: email, phone_number: phone_number, code: code.map(|code| code.to_string()) };
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct PunchclockDataParams {
+    #[serde(rename = "objectId")]
+    object_id: u64,
+
+    #[serde(rename = "defaultTimezone")]
+    default_timezone: String,
+
+    #[serde(rename = "_spirit")]
+    _spirit: String,
+}
+
+pub const DEFAULT_BASE_URL: &str = "https://app.connecteam.com";
+
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// HTTP client together with the host all requests are sent to.
+#[derive(Clone)]
+pub struct ApiClient {
+    http: Client,
+    base_url: String,
+    /// Attempts per request, transient failures are retried until these run out.
+    attempts: u32,
+    cache: ResponseCache,
+}
+
+impl ApiClient {
+    fn url(&self, path: &str) -> String {
+        return format!("{}{}", self.base_url.trim_end_matches('/'), path);
+    }
+
+    /// Sends the request, retrying timeouts, 429 and 5xx responses with a jittered exponential backoff.
+    /// Requests that change data are only retried when they cannot have reached the server,
+    /// so a shift is never added twice.
+    async fn send(&self, request: RequestBuilder, idempotent: bool) -> Result<reqwest::Response, ConnecteamError> {
+        let mut attempt = 1;
+        loop {
+            // Bodies are plain strings so the request can always be cloned.
+            let result = request.try_clone().expect("request body is not a stream").send().await;
+            let retry_after = match &result {
+                Ok(resp) if attempt < self.attempts && is_transient_status(resp.status(), idempotent) => {
+                    Some(retry_after(resp).unwrap_or_else(|| backoff_delay(attempt)))
+                }
+                Err(err) if attempt < self.attempts && is_transient_error(err, idempotent) => Some(backoff_delay(attempt)),
+                _ => None,
+            };
+            let Some(delay) = retry_after else {
+                return Ok(result?);
+            };
+            match &result {
+                Ok(resp) => log::warn!("Request failed with {}, retrying in {:.1}s", resp.status(), delay.as_secs_f64()),
+                Err(err) => log::warn!("Request failed ({}), retrying in {:.1}s", err, delay.as_secs_f64()),
+            }
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+fn is_transient_status(status: StatusCode, idempotent: bool) -> bool {
+    return status == StatusCode::TOO_MANY_REQUESTS || (idempotent && status.is_server_error());
+}
+
+fn is_transient_error(err: &reqwest::Error, idempotent: bool) -> bool {
+    return err.is_connect() || (idempotent && err.is_timeout());
+}
+
+/// Delay asked for by a 429 response, in whole seconds.
+fn retry_after(resp: &reqwest::Response) -> Option<Duration> {
+    let seconds = resp.headers().get(reqwest::header::RETRY_AFTER)?.to_str().ok()?.parse::<u64>().ok()?;
+    return Some(Duration::from_secs(seconds).min(RETRY_MAX_DELAY));
+}
+
+/// Exponential backoff with full jitter: a random delay up to 0.5s, 1s, 2s, ... after the first, second, third failure.
+fn backoff_delay(attempt: u32) -> Duration {
+    let cap = RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt - 1)).min(RETRY_MAX_DELAY);
+    // The sub-second clock is random enough to keep concurrent retries apart.
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().subsec_nanos();
+    return cap.mul_f64(nanos as f64 / 1_000_000_000.0);
+}
+
+/// Builds the HTTP client. Without `proxy` the `HTTPS_PROXY`/`HTTP_PROXY` environment variables are used,
+/// `ca_cert` is a PEM file trusted besides the system roots, e.g. of a TLS inspecting proxy.
+pub fn build_client(
+    base_url: &str,
+    attempts: u32,
+    proxy: &Option<String>,
+    ca_cert: &Option<PathBuf>,
+    cache: ResponseCache,
+) -> Result<ApiClient, ConnecteamError> {
+    // Redirects are not followed so an expired session sent to the login page shows up as such.
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(30))
+        .redirect(reqwest::redirect::Policy::none());
+    if let Some(proxy) = proxy {
+        builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+    }
+    if let Some(path) = ca_cert {
+        let pem = std::fs::read(path).map_err(|err| ConnecteamError::Config(format!("Failed to read CA certificate {:?}: {}", path, err)))?;
+        builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+    }
+    let client = builder.build()?;
+    return Ok(ApiClient { http: client, base_url: base_url.to_string(), attempts: attempts.max(1), cache: cache });
+}
+
+trait Authorize {
+    fn authorize(self, session_info: &SessionInfo) -> Self;
+}
+
+impl Authorize for RequestBuilder {
+    /// Sends the API token when one was given, the session cookies otherwise.
+    fn authorize(self, session_info: &SessionInfo) -> Self {
+        return match &session_info.token {
+            Some(token) => self.bearer_auth(token),
+            None => self.header(
+                "cookie",
+                f!("session={session_info.session}; _spirit={session_info.spirit}; "),
+            ),
+        };
+    }
+}
+
+/// The API answers expired sessions with 401/403 or a redirect to the login page.
+fn is_auth_failure(status: reqwest::StatusCode) -> bool {
+    return status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN || status.is_redirection();
+}
+
+pub async fn get_object_ids_from_api(client: &ApiClient, session_info: &SessionInfo, container: &str) -> Result<Vec<u64>, ConnecteamError> {
+    let cached = client.cache.get("ContentStructure", "", session_info);
+    let resp = match cached.clone() {
+        Some(resp) => resp,
+        None => {
+            let request = client
+                .http
+                .get(client.url("/api/UserDashboard/ContentStructure/"))
+                .authorize(session_info);
+            let resp_raw = client.send(request, true).await?;
+
+            if is_auth_failure(resp_raw.status()) {
+                return Err(ConnecteamError::Auth);
+            }
+            resp_raw.error_for_status()?.text().await?
+        }
+    };
+    log::debug!("Content structure response: {}", resp);
+
+    let parsed: Response<ContentStructure> = parse_response(&resp, "ContentStructure")?;
+    let Some(containers) = parsed.data.containers else {
+        // An expired session gets a response without any dashboard content.
+        return Err(ConnecteamError::Auth);
+    };
+    if cached.is_none() {
+        client.cache.put("ContentStructure", "", session_info, &resp);
+    }
+
+    let matching_containers: Vec<_> = containers.iter().filter(|x| x.name == container).collect();
+    if matching_containers.is_empty() {
+        let available = containers.iter().map(|x| x.name.clone()).collect::<Vec<_>>().join(", ");
+        return Err(ConnecteamError::UnknownContainer(f!("{container} (available: {available})")));
+    }
+
+    let object_ids = matching_containers
+        .iter()
+        .flat_map(|x| &x.assets)
+        .filter(|x| x.dashboard_type.as_deref() == Some("punchclock"))
+        .flat_map(|x| &x.courses)
+        .flat_map(|x| &x.sections)
+        .flat_map(|x| &x.objects)
+        .map(|x| x.id)
+        .collect::<Vec<_>>();
+
+    if object_ids.len() == 0 {
+        return Err(ConnecteamError::NoObjectId);
+    }
+    log::debug!("Resolved object ids: {:?}", object_ids);
+    return Ok(object_ids);
+}
+
+/// Fetches the timesheet of the logged in user, or of `user_id` when the session is allowed to see it.
+pub async fn send_request_get_timesheet(
+    client: &ApiClient,
+    session_info: &SessionInfo,
+    object_id: u64,
+    user_id: Option<u64>,
+    start: &NaiveDate,
+    end: &NaiveDate,
+    timezone: &Tz,
+) -> Result<String, ConnecteamError> {
+    let request_payload = TimesheetParams {
+        start_date: start.format("%Y-%m-%d").to_string(),
+        end_date: end.format("%Y-%m-%d").to_string(),
+        object_id: object_id,
+        user_id: user_id,
+        default_timezone: timezone.name().to_string(),
+        _spirit: session_info.spirit.clone(),
+    };
+
+    let body = json!(request_payload).to_string();
+    if let Some(result) = client.cache.get("Timesheet", &body, session_info) {
+        return Ok(result);
+    }
+    let request = client
+        .http
+        .post(client.url("/api/UserDashboard/PunchClock/Timesheet/"))
+        .authorize(session_info)
+        .body(body.clone());
+    let resp_raw = client.send(request, true).await?;
+
+    if is_auth_failure(resp_raw.status()) {
+        return Err(ConnecteamError::Auth);
+    }
+    let result = resp_raw.error_for_status()?.text().await?;
+    log::debug!("Timesheet response for object id {}: {}", object_id, result);
+    client.cache.put("Timesheet", &body, session_info, &result);
+    return Ok(result);
+}
+
+/// Punchclock data including the running shift. It changes whenever someone punches in the app, so it is only
+/// answered from the cache when the caller gives a `max_age`.
+pub async fn send_request_get_punchclock_data(
+    client: &ApiClient,
+    session_info: &SessionInfo,
+    object_id: u64,
+    timezone: &Tz,
+    max_age: Option<std::time::Duration>,
+) -> Result<String, ConnecteamError> {
+    let request_payload = PunchclockDataParams {
+        object_id: object_id,
+        default_timezone: timezone.name().to_string(),
+        _spirit: session_info.spirit.clone(),
+    };
+
+    let body = json!(request_payload).to_string();
+    if let Some(max_age) = max_age {
+        if let Some(result) = client.cache.get_younger_than("PunchclockData", &body, session_info, max_age) {
+            return Ok(result);
+        }
+    }
+    let request = client
+        .http
+        .post(client.url("/api/UserDashboard/PunchClock/Data/"))
+        .authorize(session_info)
+        .body(body.clone());
+    let resp_raw = client.send(request, true).await?;
+
+    if is_auth_failure(resp_raw.status()) {
+        return Err(ConnecteamError::Auth);
+    }
+    let result = resp_raw.error_for_status()?.text().await?;
+    log::debug!("Punchclock data response for object id {}: {}", object_id, result);
+    client.cache.put("PunchclockData", &body, session_info, &result);
+    return Ok(result);
+}
+
+pub async fn send_request_add_shift(client: &ApiClient, session_info: &SessionInfo, object_id: u64, shift: &NewShift, timezone: &Tz) -> Result<String, ConnecteamError> {
+    let request_payload = AddShiftParams {
+        object_id: object_id,
+        start_timestamp: shift.start.timestamp(),
+        end_timestamp: shift.end.timestamp(),
+        tag_id: shift.tag_id,
+        sub_tag_id: shift.sub_tag_id,
+        employee_notes: shift.note.clone(),
+        default_timezone: timezone.name().to_string(),
+        _spirit: session_info.spirit.clone(),
+    };
+
+    let request = client
+        .http
+        .post(client.url("/api/UserDashboard/PunchClock/AddShift/"))
+        .authorize(session_info)
+        .body(json!(request_payload).to_string());
+    let resp_raw = client.send(request, false).await?;
+
+    if is_auth_failure(resp_raw.status()) {
+        return Err(ConnecteamError::Auth);
+    }
+    let result = resp_raw.error_for_status()?.text().await?;
+    log::debug!("Add shift response for object id {}: {}", object_id, result);
+    client.cache.clear();
+    return Ok(result);
+}
+
+/// Replaces the times, tags and note of an existing shift with the ones of `shift`.
+pub async fn send_request_edit_shift(
+    client: &ApiClient,
+    session_info: &SessionInfo,
+    object_id: u64,
+    shift_id: &str,
+    shift: &NewShift,
+    timezone: &Tz,
+) -> Result<String, ConnecteamError> {
+    let request_payload = EditShiftParams {
+        object_id: object_id,
+        shift_id: shift_id.to_string(),
+        start_timestamp: shift.start.timestamp(),
+        end_timestamp: shift.end.timestamp(),
+        tag_id: shift.tag_id,
+        sub_tag_id: shift.sub_tag_id,
+        employee_notes: shift.note.clone(),
+        default_timezone: timezone.name().to_string(),
+        _spirit: session_info.spirit.clone(),
+    };
+
+    let request = client
+        .http
+        .post(client.url("/api/UserDashboard/PunchClock/EditShift/"))
+        .authorize(session_info)
+        .body(json!(request_payload).to_string());
+    // Sending the same values twice leaves the shift the same, so the edit can be retried.
+    let resp_raw = client.send(request, true).await?;
+
+    if is_auth_failure(resp_raw.status()) {
+        return Err(ConnecteamError::Auth);
+    }
+    let result = resp_raw.error_for_status()?.text().await?;
+    log::debug!("Edit shift response for shift {}: {}", shift_id, result);
+    client.cache.clear();
+    return Ok(result);
+}
+
+pub async fn send_request_delete_shift(client: &ApiClient, session_info: &SessionInfo, object_id: u64, shift_id: &str) -> Result<String, ConnecteamError> {
+    let request_payload = DeleteShiftParams {
+        object_id: object_id,
+        shift_id: shift_id.to_string(),
+        _spirit: session_info.spirit.clone(),
+    };
+
+    let request = client
+        .http
+        .post(client.url("/api/UserDashboard/PunchClock/DeleteShift/"))
+        .authorize(session_info)
+        .body(json!(request_payload).to_string());
+    // A retried delete of a shift that is already gone would fail, so it is sent once.
+    let resp_raw = client.send(request, false).await?;
+
+    if is_auth_failure(resp_raw.status()) {
+        return Err(ConnecteamError::Auth);
+    }
+    let result = resp_raw.error_for_status()?.text().await?;
+    log::debug!("Delete shift response for shift {}: {}", shift_id, result);
+    client.cache.clear();
+    return Ok(result);
+}
+
+/// Replaces the notes of the shift that is punched in on the punchclock.
+pub async fn send_request_set_shift_note(
+    client: &ApiClient,
+    session_info: &SessionInfo,
+    object_id: u64,
+    note: &str,
+    timezone: &Tz,
+) -> Result<String, ConnecteamError> {
+    let request_payload = ShiftNoteParams {
+        object_id: object_id,
+        employee_notes: note.to_string(),
+        default_timezone: timezone.name().to_string(),
+        _spirit: session_info.spirit.clone(),
+    };
+
+    let request = client
+        .http
+        .post(client.url("/api/UserDashboard/PunchClock/ActiveShift/EmployeeNotes/"))
+        .authorize(session_info)
+        .body(json!(request_payload).to_string());
+    // The whole note is sent, so setting it again leaves it the same.
+    let resp_raw = client.send(request, true).await?;
+
+    if is_auth_failure(resp_raw.status()) {
+        return Err(ConnecteamError::Auth);
+    }
+    let result = resp_raw.error_for_status()?.text().await?;
+    log::debug!("Shift note response: {}", result);
+    client.cache.clear();
+    return Ok(result);
+}
+
+pub async fn send_request_punch_in(
+    client: &ApiClient,
+    session_info: &SessionInfo,
+    object_id: u64,
+    tag_id: u64,
+    sub_tag_id: Option<u64>,
+    at: DateTime<Utc>,
+    timezone: &Tz,
+) -> Result<String, ConnecteamError> {
+    let request_payload = PunchInParams {
+        object_id: object_id,
+        timestamp: at.timestamp(),
+        tag_id: tag_id,
+        sub_tag_id: sub_tag_id,
+        default_timezone: timezone.name().to_string(),
+        _spirit: session_info.spirit.clone(),
+    };
+
+    let request = client
+        .http
+        .post(client.url("/api/UserDashboard/PunchClock/PunchIn/"))
+        .authorize(session_info)
+        .body(json!(request_payload).to_string());
+    let resp_raw = client.send(request, false).await?;
+
+    if is_auth_failure(resp_raw.status()) {
+        return Err(ConnecteamError::Auth);
+    }
+    let result = resp_raw.error_for_status()?.text().await?;
+    log::debug!("Punch in response for object id {}: {}", object_id, result);
+    client.cache.clear();
+    return Ok(result);
+}
+
+pub async fn send_request_punch_out(client: &ApiClient, session_info: &SessionInfo, object_id: u64, note: &str, at: DateTime<Utc>, timezone: &Tz) -> Result<String, ConnecteamError> {
+    let request_payload = PunchOutParams {
+        object_id: object_id,
+        timestamp: at.timestamp(),
+        employee_notes: note.to_string(),
+        default_timezone: timezone.name().to_string(),
+        _spirit: session_info.spirit.clone(),
+    };
+
+    let request = client
+        .http
+        .post(client.url("/api/UserDashboard/PunchClock/PunchOut/"))
+        .authorize(session_info)
+        .body(json!(request_payload).to_string());
+    let resp_raw = client.send(request, false).await?;
+
+    if is_auth_failure(resp_raw.status()) {
+        return Err(ConnecteamError::Auth);
+    }
+    let result = resp_raw.error_for_status()?.text().await?;
+    log::debug!("Punch out response for object id {}: {}", object_id, result);
+    client.cache.clear();
+    return Ok(result);
+}
+
+/// Asks Connecteam to send a verification code by email or SMS.
+pub async fn send_request_login_code(client: &ApiClient, identity: &LoginIdentity) -> Result<(), ConnecteamError> {
+    let request = client
+        .http
+        .post(client.url("/api/Login/SendCode/"))
+        .body(json!(identity.params(None)).to_string());
+    let resp_raw = client.send(request, false).await?;
+
+    if is_auth_failure(resp_raw.status()) {
+        return Err(ConnecteamError::Auth);
+    }
+    let result = resp_raw.error_for_status()?.text().await?;
+    log::debug!("Send code response: {}", result);
+    return Ok(());
+}
+
+/// Exchanges a verification code for a session, returns the `Set-Cookie` headers of the response.
+pub async fn send_request_verify_code(client: &ApiClient, identity: &LoginIdentity, code: &str) -> Result<Vec<String>, ConnecteamError> {
+    let request = client
+        .http
+        .post(client.url("/api/Login/VerifyCode/"))
+        .body(json!(identity.params(Some(code))).to_string());
+    let resp_raw = client.send(request, false).await?;
+
+    if is_auth_failure(resp_raw.status()) {
+        return Err(ConnecteamError::Auth);
+    }
+    let resp_raw = resp_raw.error_for_status()?;
+    let cookies = resp_raw
+        .headers()
+        .get_all(reqwest::header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+        .collect();
+    log::debug!("Verify code response: {}", resp_raw.text().await?);
+    return Ok(cookies);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caps_backoff_delay() {
+        for attempt in 1..20 {
+            let cap = RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt - 1)).min(RETRY_MAX_DELAY);
+            assert!(backoff_delay(attempt) <= cap);
+        }
+        assert!(backoff_delay(1) <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn retries_only_safe_statuses() {
+        assert!(is_transient_status(StatusCode::TOO_MANY_REQUESTS, false));
+        assert!(is_transient_status(StatusCode::BAD_GATEWAY, true));
+        assert!(!is_transient_status(StatusCode::BAD_GATEWAY, false));
+        assert!(!is_transient_status(StatusCode::NOT_FOUND, true));
+    }
+}
@@ -0,0 +1,172 @@
+//! Responses of the read-only endpoints kept on disk, so repeated runs don't ask the API again.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::config::SessionInfo;
+use crate::parse::TimesheetEntry;
+
+pub const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CacheMode {
+    /// Answer from fresh cached responses and store new ones.
+    Use,
+    /// Always ask the API but store the responses for the next run.
+    Refresh,
+    /// Neither read nor write the cache.
+    Off,
+}
+
+/// Directory of cached response bodies, one file per endpoint, parameters and session.
+#[derive(Clone, Debug)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+    mode: CacheMode,
+}
+
+pub fn default_cache_dir() -> PathBuf {
+    let mut cache_dir = home::home_dir().unwrap();
+    cache_dir.push(".cache/connectteam");
+    return cache_dir;
+}
+
+impl ResponseCache {
+    pub fn new(dir: PathBuf, ttl: Duration, mode: CacheMode) -> ResponseCache {
+        return ResponseCache { dir: dir, ttl: ttl, mode: mode };
+    }
+
+    /// File name of a response. The session is part of the key so accounts never see each other's data.
+    fn key(endpoint: &str, params: &str, session_info: &SessionInfo) -> String {
+        let mut hasher = Sha256::new();
+        for part in [endpoint, params, &session_info.session, session_info.token.as_deref().unwrap_or_default()] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        let digest = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+        return format!("{}.json", digest);
+    }
+
+    /// Cached body of the request if it is younger than the TTL.
+    pub fn get(&self, endpoint: &str, params: &str, session_info: &SessionInfo) -> Option<String> {
+        return self.get_younger_than(endpoint, params, session_info, self.ttl);
+    }
+
+    /// Cached body of the request if it is younger than `max_age`, for requests with their own freshness needs.
+    pub fn get_younger_than(&self, endpoint: &str, params: &str, session_info: &SessionInfo, max_age: Duration) -> Option<String> {
+        if self.mode != CacheMode::Use {
+            return None;
+        }
+        let path = self.dir.join(ResponseCache::key(endpoint, params, session_info));
+        let age = SystemTime::now().duration_since(std::fs::metadata(&path).ok()?.modified().ok()?).unwrap_or_default();
+        if age > max_age {
+            return None;
+        }
+        let body = std::fs::read_to_string(&path).ok()?;
+        log::debug!("Using cached {} response from {:?}", endpoint, path);
+        return Some(body);
+    }
+
+    /// Stores a successful response. Failing to write is only logged, the cache is an optimization.
+    pub fn put(&self, endpoint: &str, params: &str, session_info: &SessionInfo, body: &str) {
+        if self.mode == CacheMode::Off {
+            return;
+        }
+        let path = self.dir.join(ResponseCache::key(endpoint, params, session_info));
+        if let Err(err) = std::fs::create_dir_all(&self.dir).and_then(|_| std::fs::write(&path, body)) {
+            log::warn!("Failed to cache the {} response in {:?}: {}", endpoint, path, err);
+        }
+    }
+
+    /// Drops all cached responses, after a change to the timesheet they are out of date.
+    pub fn clear(&self) {
+        if self.mode == CacheMode::Off {
+            return;
+        }
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            if entry.path().extension().is_some_and(|extension| extension == "json") {
+                let _ = std::fs::remove_file(entry.path());
+            }
+        }
+    }
+}
+
+/// Running shift as last seen by `status` or `punch`, so the prompt fragment never has to wait for the API.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ClockState {
+    pub checked_at: DateTime<Utc>,
+    pub shift: Option<TimesheetEntry>,
+}
+
+/// Not a `.json` file, so clearing the responses after a punch leaves it alone.
+pub fn default_clock_state_path() -> PathBuf {
+    return default_cache_dir().join("clock-state");
+}
+
+/// Failing to write is only logged, like the responses the state is a copy of the API.
+pub fn save_clock_state(path: &Path, state: &ClockState) {
+    let written = serde_json::to_string(state).map_err(std::io::Error::from).and_then(|json| {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        return std::fs::write(path, json);
+    });
+    if let Err(err) = written {
+        log::warn!("Failed to save the clock state in {:?}: {}", path, err);
+    }
+}
+
+/// `None` when no state has been saved yet or it can't be read.
+pub fn load_clock_state(path: &Path) -> Option<ClockState> {
+    return serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(session: &str) -> SessionInfo {
+        return SessionInfo { session: session.to_string(), ..SessionInfo::default() };
+    }
+
+    #[test]
+    fn caches_per_session() {
+        let dir = std::env::temp_dir().join(format!("connectteam-cache-test-{}", std::process::id()));
+        let cache = ResponseCache::new(dir.clone(), DEFAULT_TTL, CacheMode::Use);
+        cache.put("Timesheet", "{}", &session("a"), "cached");
+        assert_eq!(cache.get("Timesheet", "{}", &session("a")), Some("cached".to_string()));
+        assert_eq!(cache.get("Timesheet", "{}", &session("b")), None);
+        assert_eq!(cache.get("Timesheet", "{\"objectId\":1}", &session("a")), None);
+
+        let refresh = ResponseCache::new(dir.clone(), DEFAULT_TTL, CacheMode::Refresh);
+        assert_eq!(refresh.get("Timesheet", "{}", &session("a")), None);
+
+        cache.clear();
+        assert_eq!(cache.get("Timesheet", "{}", &session("a")), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_clock_state_when_clearing() {
+        let dir = std::env::temp_dir().join(format!("connectteam-clock-test-{}", std::process::id()));
+        let path = dir.join("clock-state");
+        assert!(load_clock_state(&path).is_none());
+
+        let start = DateTime::parse_from_rfc3339("2024-03-01T08:00:00Z").unwrap().with_timezone(&Utc);
+        let shift = TimesheetEntry::test_shift(start, None);
+        save_clock_state(&path, &ClockState { checked_at: start, shift: Some(shift) });
+        ResponseCache::new(dir.clone(), DEFAULT_TTL, CacheMode::Use).clear();
+
+        let state = load_clock_state(&path).unwrap();
+        assert_eq!(state.shift.unwrap().project, "Acme");
+        assert_eq!(state.checked_at, start);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::config::AnomalyRules;
+use crate::parse::TimesheetEntry;
+
+/// Why a shift looks unusual, see `AnomalyRules`.
+#[derive(Debug, PartialEq)]
+pub enum Anomaly {
+    LongShift(chrono::Duration),
+    Weekend(Weekday),
+    EarlyStart(NaiveTime),
+    LateStart(NaiveTime),
+}
+
+impl std::fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Anomaly::LongShift(duration) => write!(f, "{}:{:02} long", duration.num_hours(), duration.num_minutes() % 60),
+            Anomaly::Weekend(weekday) => write!(f, "on a {}", weekday),
+            Anomaly::EarlyStart(time) => write!(f, "starts early at {}", time.format("%H:%M")),
+            Anomaly::LateStart(time) => write!(f, "starts late at {}", time.format("%H:%M")),
+        }
+    }
+}
+
+/// Rules the shift breaks, judged in the timezone. Running shifts have no length yet.
+pub fn find_anomalies(entry: &TimesheetEntry, timezone: &Tz, rules: &AnomalyRules) -> Vec<Anomaly> {
+    let mut anomalies = vec![];
+    let duration = entry.duration();
+    if duration > chrono::Duration::minutes((rules.max_shift_hours * 60.0).round() as i64) {
+        anomalies.push(Anomaly::LongShift(duration));
+    }
+    let start = entry.start.with_timezone(timezone);
+    if rules.weekends && matches!(start.weekday(), Weekday::Sat | Weekday::Sun) {
+        anomalies.push(Anomaly::Weekend(start.weekday()));
+    }
+    if rules.earliest_start.is_some_and(|earliest| start.time() < earliest) {
+        anomalies.push(Anomaly::EarlyStart(start.time()));
+    }
+    if rules.latest_start.is_some_and(|latest| start.time() > latest) {
+        anomalies.push(Anomaly::LateStart(start.time()));
+    }
+    return anomalies;
+}
+
+/// Index pairs of overlapping shifts, `entries` have to be sorted by start, otherwise overlaps are missed and the
+/// second shift of a pair may start first. Shifts still in progress are skipped.
+pub fn find_overlaps(entries: &[TimesheetEntry]) -> Vec<(usize, usize)> {
+    debug_assert!(entries.is_sorted_by_key(|entry| entry.start), "find_overlaps needs the shifts sorted by start");
+    let mut overlaps = vec![];
+    // The shift ending last so far, every later shift starting before its end overlaps it.
+    let mut latest: Option<(usize, DateTime<Utc>)> = None;
+    for (index, entry) in entries.iter().enumerate() {
+        let Some(end) = entry.end else { continue };
+        match latest {
+            Some((latest_index, latest_end)) => {
+                if entry.start < latest_end {
+                    overlaps.push((latest_index, index));
+                }
+                if end > latest_end {
+                    latest = Some((index, end));
+                }
+            }
+            None => latest = Some((index, end)),
+        }
+    }
+    return overlaps;
+}
+
+/// Sets the `overlap` of every finished shift to its time already covered by an earlier shift starting on the same
+/// day in the timezone. The punch times stay as they are, only the totals count less.
+pub fn mark_overlaps(entries: &mut [TimesheetEntry], timezone: &Tz) {
+    let mut order: Vec<usize> = (0..entries.len()).collect();
+    order.sort_by_key(|index| entries[*index].start);
+    // The latest end per day so far, every later shift of the day starting before it overlaps.
+    let mut latest_ends: BTreeMap<NaiveDate, DateTime<Utc>> = BTreeMap::new();
+    for index in order {
+        let entry = &mut entries[index];
+        let Some(end) = entry.end else { continue };
+        let day = entry.start.with_timezone(timezone).date_naive();
+        entry.overlap = match latest_ends.get(&day) {
+            Some(latest_end) if entry.start < *latest_end => end.min(*latest_end) - entry.start,
+            _ => chrono::Duration::zero(),
+        };
+        let latest_end = latest_ends.entry(day).or_insert(end);
+        *latest_end = (*latest_end).max(end);
+    }
+}
+
+/// Workdays from `start` to `end` without any shift starting on them in the timezone, holidays are no workdays.
+pub fn find_gaps(
+    entries: &[TimesheetEntry],
+    timezone: &Tz,
+    start: NaiveDate,
+    end: NaiveDate,
+    workdays: &[Weekday],
+    holidays: &[NaiveDate],
+) -> Vec<NaiveDate> {
+    let worked: BTreeSet<NaiveDate> = entries.iter().map(|entry| entry.start.with_timezone(timezone).date_naive()).collect();
+    return start
+        .iter_days()
+        .take_while(|date| *date <= end)
+        .filter(|date| workdays.contains(&date.weekday()) && !holidays.contains(date) && !worked.contains(date))
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn shift(start_hour: u32, end_hour: u32) -> TimesheetEntry {
+        return TimesheetEntry::test_shift(
+            Utc.with_ymd_and_hms(2023, 10, 2, start_hour, 0, 0).unwrap(),
+            Some(Utc.with_ymd_and_hms(2023, 10, 2, end_hour, 0, 0).unwrap()),
+        );
+    }
+
+    #[test]
+    fn finds_overlapping_shifts() {
+        let entries = vec![shift(8, 12), shift(9, 10), shift(11, 13), shift(13, 15)];
+        assert_eq!(find_overlaps(&entries), vec![(0, 1), (0, 2)]);
+    }
+
+    #[test]
+    fn finds_workdays_without_shifts() {
+        let day = |day: u32| NaiveDate::from_ymd_opt(2023, 10, day).unwrap();
+        let workdays = vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+        // 2023-10-02 is a Monday with a shift, Tuesday is a holiday and the weekend is no workday.
+        let gaps = find_gaps(&[shift(8, 12)], &chrono_tz::UTC, day(2), day(9), &workdays, &[day(3)]);
+        assert_eq!(gaps, vec![day(4), day(5), day(6), day(9)]);
+    }
+
+    #[test]
+    fn flags_anomalies() {
+        let rules = AnomalyRules {
+            max_shift_hours: 10.0,
+            weekends: true,
+            earliest_start: NaiveTime::from_hms_opt(7, 0, 0),
+            latest_start: NaiveTime::from_hms_opt(20, 0, 0),
+        };
+        assert_eq!(find_anomalies(&shift(8, 12), &chrono_tz::UTC, &rules), vec![]);
+        assert_eq!(
+            find_anomalies(&shift(6, 17), &chrono_tz::UTC, &rules),
+            vec![Anomaly::LongShift(chrono::Duration::hours(11)), Anomaly::EarlyStart(NaiveTime::from_hms_opt(6, 0, 0).unwrap())]
+        );
+        assert_eq!(find_anomalies(&shift(21, 23), &chrono_tz::UTC, &rules), vec![Anomaly::LateStart(NaiveTime::from_hms_opt(21, 0, 0).unwrap())]);
+
+        let mut sunday = shift(8, 12);
+        sunday.start = Utc.with_ymd_and_hms(2023, 10, 1, 8, 0, 0).unwrap();
+        sunday.end = Some(Utc.with_ymd_and_hms(2023, 10, 1, 12, 0, 0).unwrap());
+        assert_eq!(find_anomalies(&sunday, &chrono_tz::UTC, &rules), vec![Anomaly::Weekend(Weekday::Sun)]);
+        assert_eq!(Anomaly::LongShift(chrono::Duration::minutes(635)).to_string(), "10:35 long");
+    }
+
+    #[test]
+    fn marks_overlapping_time_per_day() {
+        let mut next_day = shift(8, 12);
+        next_day.start = Utc.with_ymd_and_hms(2023, 10, 3, 9, 0, 0).unwrap();
+        next_day.end = Some(Utc.with_ymd_and_hms(2023, 10, 3, 10, 0, 0).unwrap());
+        let mut entries = vec![shift(11, 13), shift(8, 12), shift(9, 10), next_day];
+        mark_overlaps(&mut entries, &chrono_tz::UTC);
+
+        // The punch times are kept, only the counted time shrinks.
+        assert_eq!(entries[0].start, Utc.with_ymd_and_hms(2023, 10, 2, 11, 0, 0).unwrap());
+        assert_eq!(entries[0].counted_duration(), chrono::Duration::hours(1));
+        assert_eq!(entries[1].counted_duration(), chrono::Duration::hours(4));
+        assert_eq!(entries[2].counted_duration(), chrono::Duration::zero());
+        // Shifts of other days don't overlap, even when the times of day do.
+        assert_eq!(entries[3].counted_duration(), chrono::Duration::hours(1));
+    }
+}
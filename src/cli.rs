@@ -0,0 +1,748 @@
+use chrono::{NaiveDate, NaiveTime};
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::api;
+use crate::config::{Browser, Defaults};
+use crate::dates::{self, RoundMode, WeekStart};
+use crate::render::{GroupBy, SortOrder, Summary};
+
+#[derive(clap::ValueEnum, Clone, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Csv,
+    /// Array of shifts with start and end (RFC 3339, end is null while punched in), desc, project, subproject and breaks
+    Json,
+    Ics,
+    Markdown,
+    Html,
+}
+
+#[derive(clap::ValueEnum, Clone, PartialEq)]
+pub enum TagsFormat {
+    Table,
+    /// Object with the tags (id, name, sub_items), shift attachments (id, kind, title, required) and active_shift
+    Json,
+}
+
+#[derive(clap::ValueEnum, Clone, PartialEq)]
+pub enum StatusFormat {
+    /// Sentence with the start and project of the running shift, exits with 6 when clocked out
+    Text,
+    /// Elapsed time and project on one line, for i3status, polybar and similar status bars
+    Line,
+    /// Object with text, tooltip and class for a waybar custom module with "return-type": "json"
+    Waybar,
+    /// Short fragment like "⏱ 3h12m Acme" for PS1 or starship, read from the clock state of the last status or punch
+    Prompt,
+}
+
+#[derive(clap::ValueEnum, Clone, PartialEq)]
+pub enum ReportKind {
+    /// Self-contained HTML page with the shifts by day and the hours per project
+    Html,
+    /// Printable timesheet with one row per day and a signature line
+    Pdf,
+}
+
+#[derive(clap::ValueEnum, Clone, PartialEq)]
+pub enum InvoiceFormat {
+    /// Plain text from invoice.txt in ~/.config/connectteam, or the built-in layout
+    Text,
+    /// HTML page from invoice.html in ~/.config/connectteam, or the built-in layout
+    Html,
+    Pdf,
+}
+
+#[derive(clap::ValueEnum, Clone, PartialEq)]
+pub enum ExportKind {
+    /// Excel workbook with one sheet per month and formulas for the day and week totals
+    Xlsx,
+}
+
+#[derive(clap::ValueEnum, Clone, PartialEq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Show the logged shifts for a date range (the default without a subcommand)
+    Timesheet(TimesheetArgs),
+
+    /// List the projects and subprojects that shifts can be tagged with
+    Tags {
+        #[clap(short, long, value_enum, default_value = "table")]
+        format: TagsFormat,
+    },
+
+    /// Log in again, with a verification code or by pasting session cookies
+    Login(LoginArgs),
+
+    /// Log a shift that was not punched in the app
+    Add(AddArgs),
+
+    /// Add shifts you forgot to punch or correct logged ones
+    Shift {
+        #[clap(subcommand)]
+        action: ShiftAction,
+    },
+
+    /// Show whether you are clocked in, exits with 6 when clocked out
+    Status(StatusArgs),
+
+    /// Write a standalone report of a month's shifts to a file
+    Report(ReportArgs),
+
+    /// Export the shifts of one or more months to a file
+    Export(ExportArgs),
+
+    /// Clock in or out right now
+    Punch {
+        #[clap(subcommand)]
+        action: PunchAction,
+    },
+
+    /// Add to the note of the running shift, e.g. note "Reviewing the release"
+    Note(NoteArgs),
+
+    /// Show the total hours per project, subproject, week and month
+    Summary(SummaryArgs),
+
+    /// Show average punch in and out times, shift length, the longest day and the hours per weekday
+    Stats(StatsArgs),
+
+    /// Compare the worked hours with the contracted hours from the [contract] section of the defaults file
+    Overtime(OvertimeArgs),
+
+    /// Multiply the hours per project with the rates from the [rates] section of the defaults file
+    Earnings(EarningsArgs),
+
+    /// Write a numbered invoice for the hours of one client, billed with the [rates] of the defaults file
+    Invoice(InvoiceArgs),
+
+    /// Fetch the shifts changed since the last sync into the local mirror used by --offline
+    Sync(SyncArgs),
+
+    /// Browse a month calendar with the shifts per day, filter them and punch in or out
+    Tui(TuiArgs),
+
+    /// Look for problems in the logged shifts, exits with 7 when something is found
+    Check {
+        #[clap(subcommand)]
+        action: CheckAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CheckAction {
+    /// List the workdays without any shift, the holidays of the defaults file are skipped
+    Gaps(CheckArgs),
+
+    /// List the pairs of shifts whose times overlap, e.g. after corrections in the web app
+    Overlaps(CheckArgs),
+
+    /// List the shifts breaking the [anomalies] rules of the defaults file, e.g. too long or on weekends
+    Anomalies(CheckArgs),
+}
+
+#[derive(Subcommand)]
+pub enum ShiftAction {
+    /// Add a shift on one day, e.g. --date 2024-05-02 --from 09:00 --to 17:30 --tag acme/back
+    Add(ShiftAddArgs),
+
+    /// Change the times, tag or note of a shift, the ids are in the ID column of the timesheet
+    Edit(ShiftEditArgs),
+
+    /// Remove a shift, e.g. a duplicate punch
+    Delete(ShiftDeleteArgs),
+}
+
+#[derive(Subcommand)]
+pub enum PunchAction {
+    /// Start a shift on a project
+    In(PunchInArgs),
+
+    /// End the running shift
+    Out(PunchOutArgs),
+}
+
+#[derive(Parser)]
+#[clap(after_help = "Defaults for the timezone, config path and timesheet options can be set in ~/.config/connectteam.toml.\n\nExit codes: 0 success, 1 other errors, 2 authentication failed, 3 network error, 4 unexpected API response, 5 config or file error, 6 clocked out (status), 7 problems found (check)")]
+pub struct Cli {
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    /// Options of the timesheet report shown when no subcommand is given
+    #[clap(flatten)]
+    pub timesheet: TimesheetArgs,
+
+    /// Host of the Connecteam API, e.g. a regional endpoint or a mock server
+    #[clap(long, global = true, env = "CONNECTTEAM_BASE_URL", default_value = api::DEFAULT_BASE_URL)]
+    pub base_url: String,
+
+    /// Proxy URL for all requests, e.g. http://proxy.example.com:3128. Defaults to HTTPS_PROXY or HTTP_PROXY
+    #[clap(long, global = true)]
+    pub proxy: Option<String>,
+
+    /// PEM file with a CA certificate to trust besides the system ones, e.g. of a TLS inspecting proxy
+    #[clap(long, global = true, env = "CONNECTTEAM_CA_CERT")]
+    pub ca_cert: Option<PathBuf>,
+
+    /// Show the shifts last fetched into the local mirror (~/.local/share/connectteam/mirror.db) without asking the API
+    #[clap(long, global = true)]
+    pub offline: bool,
+
+    /// Ask the API instead of answering from cached responses, and don't cache the new ones
+    #[clap(long, global = true, conflicts_with = "refresh")]
+    pub no_cache: bool,
+
+    /// Ask the API instead of answering from cached responses, and cache the new ones
+    #[clap(long, global = true)]
+    pub refresh: bool,
+
+    /// Seconds the timesheet and content structure responses are cached in ~/.cache/connectteam
+    #[clap(long, global = true, default_value = "300")]
+    pub cache_ttl: u64,
+
+    /// Tries per API request, timeouts, 429 and 5xx responses are retried with a growing delay in between
+    #[clap(long, global = true, env = "CONNECTTEAM_ATTEMPTS", default_value = "3")]
+    pub attempts: u32,
+
+    /// Path of the session config file, defaults to ~/.config/connectteam.json. The session goes to the OS keyring when one is available, unless logged in with --encrypt
+    #[clap(long, global = true, env = "CONNECTTEAM_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// IANA timezone name (e.g. America/New_York), defaults to the system timezone
+    #[clap(short, long, global = true)]
+    pub timezone: Option<String>,
+
+    /// Highlight weekends and long shifts in the table
+    #[clap(long, global = true, value_enum, default_value = "auto")]
+    pub color: ColorChoice,
+
+    /// Write the output to a file instead of stdout
+    #[clap(short, long, global = true)]
+    pub output: Option<PathBuf>,
+
+    /// API token sent as a bearer token instead of the stored session cookies
+    #[clap(long, global = true, env = "CONNECTTEAM_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+
+    /// Keep the session cookies in memory only instead of storing them in the keyring or config file
+    #[clap(long, global = true)]
+    pub no_save: bool,
+
+    /// Name of the container holding the punchclock, use with --refresh-object-id when changing it
+    #[clap(long, global = true, default_value = "Operations")]
+    pub container: String,
+
+    /// Look up the punchclock object ids again instead of using the stored ones
+    #[clap(long, global = true)]
+    pub refresh_object_id: bool,
+
+    /// Use only this punchclock object id instead of all of them
+    #[clap(long, global = true)]
+    pub object_id: Option<u64>,
+
+    /// Don't print warnings and progress messages, only the report and errors
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Log details to stderr, repeat (-vv) to include the raw API responses
+    #[clap(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+}
+
+/// Days a command looks at, given as a start and end or one of the shortcuts.
+#[derive(Args)]
+#[clap(group(clap::ArgGroup::new("range_shortcut").multiple(false).conflicts_with_all(["start", "end"])))]
+pub struct RangeArgs {
+    /// First day to show, e.g. "2024-03-01" or "2 weeks ago"
+    #[clap(short, long, visible_alias = "from", default_value = "7 days ago")]
+    pub start: String,
+
+    /// Last day to show
+    #[clap(short, long, visible_alias = "to", default_value = "today")]
+    pub end: String,
+
+    /// Show a whole calendar month, e.g. 2024-03
+    #[clap(long, group = "range_shortcut", value_parser = dates::parse_month)]
+    pub month: Option<NaiveDate>,
+
+    /// Show the current week
+    #[clap(long, group = "range_shortcut")]
+    pub this_week: bool,
+
+    /// Show the previous week
+    #[clap(long, group = "range_shortcut")]
+    pub last_week: bool,
+
+    /// Show the current calendar month
+    #[clap(long, group = "range_shortcut")]
+    pub this_month: bool,
+
+    /// Show the previous calendar month
+    #[clap(long, group = "range_shortcut")]
+    pub last_month: bool,
+
+    /// First day of the week used by --this-week, --last-week, --overtime-threshold and the weekly totals
+    #[clap(long, value_enum, default_value = "monday")]
+    pub week_start: WeekStart,
+}
+
+#[derive(Args)]
+pub struct TimesheetArgs {
+    #[clap(flatten)]
+    pub range: RangeArgs,
+
+    /// Leave the time shifts of the same day overlap out of the totals, the shifts are still shown as punched
+    #[clap(long)]
+    pub dedup_overlaps: bool,
+
+    /// Warn about weeks with more logged hours than this
+    #[clap(long)]
+    pub overtime_threshold: Option<f64>,
+
+    #[clap(short, long, value_enum, default_value = "table")]
+    pub format: OutputFormat,
+
+    /// Browse the shifts in an interactive terminal UI
+    #[clap(long, conflicts_with_all = ["format", "summary"])]
+    pub tui: bool,
+
+    /// Redraw the table every this many seconds, with the running shift's duration ticking up
+    #[clap(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..), conflicts_with = "tui")]
+    pub watch: Option<u64>,
+
+    /// Round punch in and out times to this many minutes before showing and adding them up
+    #[clap(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub round: Option<u32>,
+
+    /// Direction used by --round
+    #[clap(long, value_enum, default_value = "nearest", requires = "round")]
+    pub round_mode: RoundMode,
+
+    /// Show total hours per project (or project and subproject) instead of individual shifts
+    #[clap(long, value_enum)]
+    pub summary: Option<Summary>,
+
+    /// Hourly rate used to show the estimated pay next to the totals
+    #[clap(long)]
+    pub rate: Option<f64>,
+
+    /// Currency code shown with the estimated pay, e.g. EUR
+    #[clap(long, requires = "rate")]
+    pub currency: Option<String>,
+
+    /// Width of the table in characters, defaults to the terminal width
+    #[clap(long)]
+    pub width: Option<usize>,
+
+    /// Group the shifts in the table by day, ISO week, project or not at all
+    #[clap(long, value_enum, default_value = "day")]
+    pub group_by: GroupBy,
+
+    /// Order of the days and shifts in the table
+    #[clap(long, value_enum, default_value = "desc")]
+    pub sort: SortOrder,
+
+    /// Fetch the timesheet of this user id instead of your own, needs manager access
+    #[clap(long)]
+    pub user: Option<u64>,
+
+    /// Read a saved timesheet response instead of calling the API
+    #[clap(long)]
+    pub from_file: Option<PathBuf>,
+
+    /// Save the raw timesheet response to this file
+    #[clap(long)]
+    pub save_response: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+}
+
+/// Restricts a command to the shifts of some projects.
+#[derive(Args)]
+pub struct FilterArgs {
+    /// Only include shifts whose project contains this text (case-insensitive)
+    #[clap(long)]
+    pub project: Option<String>,
+
+    /// Only include shifts whose subproject contains this text (case-insensitive)
+    #[clap(long)]
+    pub subproject: Option<String>,
+
+    /// Only include shifts whose description matches this regular expression, e.g. "PROJ-\d+" or "(?i)review"
+    #[clap(long)]
+    pub grep: Option<regex::Regex>,
+}
+
+#[derive(Args)]
+pub struct LoginArgs {
+    /// Email address to send a verification code to, instead of pasting cookies
+    #[clap(long, conflicts_with = "phone")]
+    pub email: Option<String>,
+
+    /// Phone number to send a verification code to by SMS, e.g. +48123456789
+    #[clap(long)]
+    pub phone: Option<String>,
+
+    /// Take the session cookies from this browser's cookie store instead of pasting them
+    #[clap(long, value_enum, conflicts_with_all = ["email", "phone"])]
+    pub from_browser: Option<Browser>,
+
+    /// Keep the session in a file encrypted with a passphrase, even when an OS keyring is available.
+    /// Later runs ask for it or read it from CONNECTTEAM_PASSPHRASE
+    #[clap(long)]
+    pub encrypt: bool,
+}
+
+#[derive(Args)]
+pub struct AddArgs {
+    /// Start of the shift, e.g. "yesterday 9:00"
+    #[clap(long)]
+    pub start: String,
+
+    /// End of the shift, e.g. "yesterday 17:30"
+    #[clap(long)]
+    pub end: String,
+
+    /// Project name as shown in the app (case-insensitive)
+    #[clap(long)]
+    pub project: String,
+
+    /// Subproject name as shown in the app (case-insensitive)
+    #[clap(long)]
+    pub subproject: Option<String>,
+
+    /// Note attached to the shift
+    #[clap(long, default_value = "")]
+    pub note: String,
+}
+
+#[derive(Args)]
+pub struct ShiftAddArgs {
+    /// Day of the shift, e.g. "2024-05-02" or "yesterday"
+    #[clap(long, default_value = "today")]
+    pub date: String,
+
+    /// Punch in time, e.g. 09:00
+    #[clap(long)]
+    pub from: NaiveTime,
+
+    /// Punch out time, e.g. 17:30. A time before --from ends the shift on the next day, the same time is refused
+    #[clap(long)]
+    pub to: NaiveTime,
+
+    /// Project and subproject typed loosely, e.g. "acme/back"
+    #[clap(long)]
+    pub tag: String,
+
+    /// Note attached to the shift
+    #[clap(long, default_value = "")]
+    pub note: String,
+}
+
+#[derive(Args)]
+pub struct ShiftEditArgs {
+    /// Id of the shift
+    pub id: String,
+
+    /// Day the shift started on, without it the last two months are searched
+    #[clap(long)]
+    pub date: Option<String>,
+
+    /// New start, e.g. "09:00" on the same day or "2024-05-02 09:00"
+    // Own ids, so the configured range defaults for `start` and `end` never reach the shift.
+    #[clap(long = "start", id = "new_start")]
+    pub start: Option<String>,
+
+    /// New end, e.g. "17:30" on the day of the start or "2024-05-03 01:00"
+    #[clap(long = "end", id = "new_end")]
+    pub end: Option<String>,
+
+    /// New project and subproject typed loosely, e.g. "acme/back"
+    #[clap(long)]
+    pub tag: Option<String>,
+
+    /// New note of the shift
+    #[clap(long)]
+    pub note: Option<String>,
+
+    /// Edit the current values in $EDITOR and confirm the changes before they are submitted
+    #[clap(short, long, conflicts_with_all = ["new_start", "new_end", "tag", "note"])]
+    pub interactive: bool,
+}
+
+#[derive(Args)]
+pub struct ShiftDeleteArgs {
+    /// Id of the shift
+    pub id: String,
+
+    /// Day the shift started on, without it the last two months are searched
+    #[clap(long)]
+    pub date: Option<String>,
+
+    /// Delete without asking for confirmation
+    #[clap(short, long)]
+    pub yes: bool,
+}
+
+#[derive(Args)]
+pub struct PunchInArgs {
+    /// Project name as shown in the app (case-insensitive), picked from a list when left out
+    #[clap(long)]
+    pub project: Option<String>,
+
+    /// Subproject name as shown in the app (case-insensitive)
+    #[clap(long, requires = "project")]
+    pub subproject: Option<String>,
+
+    /// Project and subproject typed loosely, e.g. "acme/back", instead of --project and --subproject
+    #[clap(long, conflicts_with = "project")]
+    pub tag: Option<String>,
+}
+
+#[derive(Args)]
+pub struct PunchOutArgs {
+    /// Note attached to the shift
+    #[clap(long, default_value = "")]
+    pub note: String,
+}
+
+#[derive(Args)]
+pub struct NoteArgs {
+    /// Text added to the note, after what is already there separated by "; "
+    pub text: String,
+
+    /// Replace the note instead of adding to it
+    #[clap(long)]
+    pub replace: bool,
+}
+
+#[derive(Args)]
+pub struct ReportArgs {
+    #[clap(value_enum)]
+    pub kind: ReportKind,
+
+    /// Month to report on, e.g. 2024-03, defaults to the current month
+    #[clap(long, value_parser = dates::parse_month)]
+    pub month: Option<NaiveDate>,
+
+    /// Employee name printed on the PDF report
+    #[clap(long)]
+    pub employee: Option<String>,
+
+    /// Hourly rate used to show the estimated pay next to the total
+    #[clap(long)]
+    pub rate: Option<f64>,
+
+    /// Currency code shown with the estimated pay, e.g. EUR
+    #[clap(long, requires = "rate")]
+    pub currency: Option<String>,
+
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    #[clap(value_enum)]
+    pub kind: ExportKind,
+
+    /// First month to export, e.g. 2024-01, defaults to the current month
+    #[clap(long, value_parser = dates::parse_month)]
+    pub from: Option<NaiveDate>,
+
+    /// Last month to export, defaults to --from
+    #[clap(long, value_parser = dates::parse_month)]
+    pub to: Option<NaiveDate>,
+
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+}
+
+#[derive(Args)]
+pub struct SummaryArgs {
+    #[clap(flatten)]
+    pub range: RangeArgs,
+
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+
+    /// Hourly rate used to show the estimated pay next to the totals
+    #[clap(long)]
+    pub rate: Option<f64>,
+
+    /// Currency code shown with the estimated pay, e.g. EUR
+    #[clap(long, requires = "rate")]
+    pub currency: Option<String>,
+}
+
+#[derive(Args)]
+pub struct StatsArgs {
+    #[clap(flatten)]
+    pub range: RangeArgs,
+
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+}
+
+#[derive(Args)]
+pub struct OvertimeArgs {
+    #[clap(flatten)]
+    pub range: RangeArgs,
+}
+
+#[derive(Args)]
+pub struct EarningsArgs {
+    #[clap(flatten)]
+    pub range: RangeArgs,
+
+    #[clap(flatten)]
+    pub filter: FilterArgs,
+}
+
+#[derive(Args)]
+pub struct InvoiceArgs {
+    /// Client to bill, the shifts of projects containing this text (case-insensitive) become line items
+    #[clap(long)]
+    pub client: String,
+
+    /// Name and address of the client printed on the invoice, defaults to --client
+    #[clap(long)]
+    pub address: Option<String>,
+
+    #[clap(flatten)]
+    pub range: RangeArgs,
+
+    #[clap(short, long, value_enum, default_value = "pdf")]
+    pub format: InvoiceFormat,
+
+    /// Invoice number to use instead of the next one from the counter, the counter is left alone
+    #[clap(long)]
+    pub number: Option<String>,
+}
+
+#[derive(Args)]
+pub struct SyncArgs {
+    /// First day fetched by the first sync or with --full, e.g. "2024-01-01" or "90 days ago"
+    #[clap(long, default_value = "90 days ago")]
+    pub since: String,
+
+    /// Days before the last sync that are fetched again, shifts are often corrected afterwards
+    #[clap(long, default_value = "7")]
+    pub lookback: i64,
+
+    /// Fetch everything since --since again instead of continuing from the last sync
+    #[clap(long)]
+    pub full: bool,
+}
+
+#[derive(Args)]
+pub struct StatusArgs {
+    #[clap(short, long, value_enum, default_value = "text")]
+    pub format: StatusFormat,
+
+    /// Show the status again every this many seconds. The text is redrawn, the other formats print a line each time
+    #[clap(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub watch: Option<u64>,
+
+    /// Seconds the cached clock state may be old, defaults to 30 for the status bar formats and 0 for text
+    #[clap(long)]
+    pub max_age: Option<u64>,
+}
+
+#[derive(Args)]
+pub struct TuiArgs {
+    /// Month shown first, e.g. 2024-03, defaults to the current one
+    #[clap(long, value_parser = dates::parse_month)]
+    pub month: Option<NaiveDate>,
+
+    /// First day of the calendar weeks
+    #[clap(long, value_enum, default_value = "monday")]
+    pub week_start: WeekStart,
+}
+
+#[derive(Args)]
+pub struct CheckArgs {
+    #[clap(flatten)]
+    pub range: RangeArgs,
+}
+
+/// Subcommands flattening `RangeArgs`, nested ones separated by a space.
+const RANGE_SUBCOMMANDS: [&str; 9] = ["timesheet", "summary", "stats", "overtime", "earnings", "invoice", "check gaps", "check overlaps", "check anomalies"];
+
+/// Subcommands besides the top level that take the configured default of an option. Other subcommands may have an
+/// option with the same id but a different meaning, e.g. the required `add --start` or `status --format`.
+fn defaulted_subcommands(id: &str) -> Vec<&'static str> {
+    return match id {
+        "start" | "end" => RANGE_SUBCOMMANDS.to_vec(),
+        "week_start" => RANGE_SUBCOMMANDS.into_iter().chain(["tui"]).collect(),
+        "format" | "width" | "group_by" | "sort" => vec!["timesheet"],
+        _ => vec![],
+    };
+}
+
+/// Sets the default value of the option on the subcommand at `path`, e.g. ["check", "gaps"].
+fn set_subcommand_default(command: clap::Command, path: &[&str], id: &'static str, value: &'static str) -> clap::Command {
+    let Some((name, rest)) = path.split_first() else {
+        return command.mut_arg(id, |arg| arg.default_value(value));
+    };
+    return command.mut_subcommand(*name, |subcommand| set_subcommand_default(subcommand, rest, id, value));
+}
+
+/// The command line with the configured defaults in place of the built-in default values.
+fn command_with_defaults(defaults: &Defaults) -> clap::Command {
+    let mut command = Cli::command();
+    for (id, value) in defaults.values() {
+        // Clap needs default values that live as long as the program.
+        let value: &'static str = Box::leak(value.into_boxed_str());
+        command = command.mut_arg(id, |arg| arg.default_value(value));
+        for subcommand in defaulted_subcommands(id) {
+            let path: Vec<&str> = subcommand.split(' ').collect();
+            command = set_subcommand_default(command, &path, id, value);
+        }
+    }
+    return command;
+}
+
+/// Parses the command line with the configured defaults in place of the built-in default values.
+pub fn parse_with_defaults(defaults: &Defaults) -> Cli {
+    return Cli::from_arg_matches(&command_with_defaults(defaults).get_matches()).unwrap_or_else(|err| err.exit());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_defaults_only_to_range_and_timesheet_subcommands() {
+        let defaults = Defaults {
+            start: Some("30 days ago".to_string()),
+            end: Some("today".to_string()),
+            format: Some("markdown".to_string()),
+            ..Default::default()
+        };
+        let parse = |args: &[&str]| Cli::from_arg_matches(&command_with_defaults(&defaults).try_get_matches_from(args)?);
+
+        let Some(Command::Timesheet(timesheet)) = parse(&["connectteam", "timesheet"]).unwrap().command else { panic!("expected timesheet") };
+        assert_eq!(timesheet.range.start, "30 days ago");
+        assert!(timesheet.format == OutputFormat::Markdown);
+        let Some(Command::Check { action: CheckAction::Gaps(check) }) = parse(&["connectteam", "check", "gaps"]).unwrap().command else { panic!("expected check gaps") };
+        assert_eq!(check.range.start, "30 days ago");
+
+        // Options with the same id elsewhere keep their own defaults and requirements.
+        let Some(Command::Tags { format }) = parse(&["connectteam", "tags"]).unwrap().command else { panic!("expected tags") };
+        assert!(format == TagsFormat::Table);
+        assert!(parse(&["connectteam", "add", "--end", "today 17:00", "--project", "Acme"]).is_err());
+        assert!(parse(&["connectteam", "add", "--start", "today 9:00", "--end", "today 17:00", "--project", "Acme"]).is_ok());
+        let Some(Command::Shift { action: ShiftAction::Edit(edit) }) = parse(&["connectteam", "shift", "edit", "abc", "--tag", "acme"]).unwrap().command else { panic!("expected shift edit") };
+        assert_eq!(edit.start, None);
+        assert_eq!(edit.end, None);
+    }
+}
@@ -0,0 +1,93 @@
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+
+use crate::api::{
+    get_object_ids_from_api, send_request_add_shift, send_request_delete_shift, send_request_edit_shift, send_request_get_punchclock_data,
+    send_request_get_timesheet, send_request_punch_in, send_request_punch_out, send_request_set_shift_note, ApiClient, NewShift,
+};
+use crate::config::SessionInfo;
+use crate::error::ConnecteamError;
+use crate::parse::{parse_punchclock, parse_timesheet, PunchclockMeta, TimesheetEntry};
+
+/// Connecteam API bound to one session, the requests return parsed responses.
+pub struct ConnecteamClient {
+    api: ApiClient,
+    session_info: SessionInfo,
+}
+
+impl ConnecteamClient {
+    pub fn new(api: ApiClient, session_info: SessionInfo) -> ConnecteamClient {
+        return ConnecteamClient { api: api, session_info: session_info };
+    }
+
+    pub fn session_info(&self) -> &SessionInfo {
+        return &self.session_info;
+    }
+
+    /// Punchclock object ids found in the content structure of the named container.
+    pub async fn content_structure(&self, container: &str) -> Result<Vec<u64>, ConnecteamError> {
+        return get_object_ids_from_api(&self.api, &self.session_info, container).await;
+    }
+
+    /// Raw timesheet response, for callers that keep it around besides parsing it.
+    pub async fn timesheet_response(
+        &self,
+        object_id: u64,
+        user_id: Option<u64>,
+        start: &NaiveDate,
+        end: &NaiveDate,
+        timezone: &Tz,
+    ) -> Result<String, ConnecteamError> {
+        return send_request_get_timesheet(&self.api, &self.session_info, object_id, user_id, start, end, timezone).await;
+    }
+
+    pub async fn timesheet(&self, object_id: u64, user_id: Option<u64>, start: &NaiveDate, end: &NaiveDate, timezone: &Tz) -> Result<Vec<TimesheetEntry>, ConnecteamError> {
+        return parse_timesheet(self.timesheet_response(object_id, user_id, start, end, timezone).await?);
+    }
+
+    pub async fn punchclock_data(&self, object_id: u64, timezone: &Tz) -> Result<PunchclockMeta, ConnecteamError> {
+        return parse_punchclock(send_request_get_punchclock_data(&self.api, &self.session_info, object_id, timezone, None).await?);
+    }
+
+    /// Punchclock data that may be up to `max_age` old, for frequent polling like status bars.
+    pub async fn cached_punchclock_data(&self, object_id: u64, timezone: &Tz, max_age: std::time::Duration) -> Result<PunchclockMeta, ConnecteamError> {
+        return parse_punchclock(send_request_get_punchclock_data(&self.api, &self.session_info, object_id, timezone, Some(max_age)).await?);
+    }
+
+    pub async fn add_shift(&self, object_id: u64, shift: &NewShift, timezone: &Tz) -> Result<(), ConnecteamError> {
+        send_request_add_shift(&self.api, &self.session_info, object_id, shift, timezone).await?;
+        return Ok(());
+    }
+
+    pub async fn edit_shift(&self, object_id: u64, shift_id: &str, shift: &NewShift, timezone: &Tz) -> Result<(), ConnecteamError> {
+        send_request_edit_shift(&self.api, &self.session_info, object_id, shift_id, shift, timezone).await?;
+        return Ok(());
+    }
+
+    pub async fn delete_shift(&self, object_id: u64, shift_id: &str) -> Result<(), ConnecteamError> {
+        send_request_delete_shift(&self.api, &self.session_info, object_id, shift_id).await?;
+        return Ok(());
+    }
+
+    pub async fn set_shift_note(&self, object_id: u64, note: &str, timezone: &Tz) -> Result<(), ConnecteamError> {
+        send_request_set_shift_note(&self.api, &self.session_info, object_id, note, timezone).await?;
+        return Ok(());
+    }
+
+    pub async fn punch_in(
+        &self,
+        object_id: u64,
+        tag_id: u64,
+        sub_tag_id: Option<u64>,
+        at: chrono::DateTime<chrono::Utc>,
+        timezone: &Tz,
+    ) -> Result<(), ConnecteamError> {
+        send_request_punch_in(&self.api, &self.session_info, object_id, tag_id, sub_tag_id, at, timezone).await?;
+        return Ok(());
+    }
+
+    pub async fn punch_out(&self, object_id: u64, note: &str, at: chrono::DateTime<chrono::Utc>, timezone: &Tz) -> Result<(), ConnecteamError> {
+        send_request_punch_out(&self.api, &self.session_info, object_id, note, at, timezone).await?;
+        return Ok(());
+    }
+}
@@ -0,0 +1,662 @@
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::api::{get_object_ids_from_api, send_request_login_code, send_request_verify_code, ApiClient, LoginIdentity};
+use crate::error::ConnecteamError;
+
+/// Service name of the keyring entries, the account is the config file path.
+const KEYRING_SERVICE: &str = "connectteam-cli";
+
+/// Passphrase of an encrypted config file, asked for at most once per run and reused when saving.
+static PASSPHRASE: OnceLock<String> = OnceLock::new();
+
+/// Version written to new config files, bump it together with a step in `migrate_session_info`.
+const CONFIG_VERSION: u64 = 1;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct SessionInfo {
+    pub version: u64,
+    pub session: String,
+    pub spirit: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub object_ids: Vec<u64>,
+
+    /// Email or phone number the session was created for with a verification code, used to log in again when it expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phone: Option<String>,
+
+    /// API token given on the command line, never written to the config file.
+    #[serde(skip)]
+    pub token: Option<String>,
+}
+
+pub fn default_session_info_path() -> PathBuf {
+    let mut session_info_file = home::home_dir().unwrap();
+    session_info_file.push(".config/connectteam.json");
+    return session_info_file;
+}
+
+/// Defaults for command line options, read from `~/.config/connectteam.toml`. Options given on the command line win.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Defaults {
+    pub config: Option<String>,
+    pub timezone: Option<String>,
+    pub color: Option<String>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub format: Option<String>,
+    pub width: Option<usize>,
+    pub group_by: Option<String>,
+    pub sort: Option<String>,
+    pub week_start: Option<String>,
+
+    /// Contracted hours the overtime is computed against.
+    pub contract: Option<Contract>,
+
+    /// Hourly rates keyed by project, or by "project / subproject" for a subproject billed differently.
+    pub rates: BTreeMap<String, ProjectRate>,
+
+    pub invoice: InvoiceSettings,
+
+    /// Days off that are no workdays for `check gaps`, e.g. ["2024-12-25", "2024-12-26"].
+    pub holidays: Vec<chrono::NaiveDate>,
+
+    pub anomalies: AnomalyRules,
+}
+
+/// `[anomalies]` section, shifts breaking a rule are highlighted in the timesheet and listed by `check anomalies`.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnomalyRules {
+    /// Shifts longer than this, breaks included.
+    pub max_shift_hours: f64,
+    /// Flag shifts starting on a Saturday or Sunday.
+    pub weekends: bool,
+    /// Flag shifts starting before this time of day, e.g. "06:00".
+    pub earliest_start: Option<chrono::NaiveTime>,
+    /// Flag shifts starting after this time of day, e.g. "20:00".
+    pub latest_start: Option<chrono::NaiveTime>,
+}
+
+impl Default for AnomalyRules {
+    fn default() -> Self {
+        return AnomalyRules { max_shift_hours: 10.0, weekends: false, earliest_start: None, latest_start: None };
+    }
+}
+
+/// `[invoice]` section, the templates live in the config directory as `invoice.txt` and `invoice.html`.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct InvoiceSettings {
+    /// Name and address printed as the sender, lines separated by newlines.
+    pub issuer: String,
+    /// Put before the zero-padded invoice number, e.g. "INV-" gives INV-0001.
+    pub prefix: String,
+}
+
+impl Default for InvoiceSettings {
+    fn default() -> Self {
+        return InvoiceSettings { issuer: "".to_string(), prefix: "INV-".to_string() };
+    }
+}
+
+/// Entry of the `[rates]` section, e.g. `Acme = { rate = 80, currency = "EUR" }`.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectRate {
+    pub rate: f64,
+    pub currency: Option<String>,
+}
+
+/// `[contract]` section of the defaults file. Either hours are enough, the other follows from the workdays.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Contract {
+    pub hours_per_day: Option<f64>,
+    pub hours_per_week: Option<f64>,
+
+    /// Days with contracted hours, e.g. ["mon", "tue", "wed", "thu", "fri"].
+    #[serde(default = "default_workdays")]
+    pub workdays: Vec<chrono::Weekday>,
+}
+
+fn default_workdays() -> Vec<chrono::Weekday> {
+    return vec![chrono::Weekday::Mon, chrono::Weekday::Tue, chrono::Weekday::Wed, chrono::Weekday::Thu, chrono::Weekday::Fri];
+}
+
+impl Contract {
+    /// Contracted time on `weekday`, zero on days off.
+    pub fn hours_on(&self, weekday: chrono::Weekday) -> Result<chrono::Duration, ConnecteamError> {
+        if !self.workdays.contains(&weekday) {
+            return Ok(chrono::Duration::zero());
+        }
+        let hours = match (self.hours_per_day, self.hours_per_week) {
+            (Some(hours_per_day), _) => hours_per_day,
+            (None, Some(hours_per_week)) => hours_per_week / self.workdays.len() as f64,
+            (None, None) => return Err(ConnecteamError::Config("the [contract] section needs hours_per_day or hours_per_week".to_string())),
+        };
+        return Ok(chrono::Duration::minutes((hours * 60.0).round() as i64));
+    }
+}
+
+impl Defaults {
+    /// Workdays of the [contract] section, Monday to Friday without one.
+    pub fn workdays(&self) -> Vec<chrono::Weekday> {
+        return match &self.contract {
+            Some(contract) => contract.workdays.clone(),
+            None => default_workdays(),
+        };
+    }
+
+    /// Configured values keyed by the id of the option they are the default for.
+    pub fn values(&self) -> Vec<(&'static str, String)> {
+        let values = [
+            ("config", self.config.clone()),
+            ("timezone", self.timezone.clone()),
+            ("color", self.color.clone()),
+            ("start", self.start.clone()),
+            ("end", self.end.clone()),
+            ("format", self.format.clone()),
+            ("width", self.width.map(|width| width.to_string())),
+            ("group_by", self.group_by.clone()),
+            ("sort", self.sort.clone()),
+            ("week_start", self.week_start.clone()),
+        ];
+        return values.into_iter().filter_map(|(id, value)| Some((id, value?))).collect();
+    }
+}
+
+impl SessionInfo {
+    pub fn login_identity(&self) -> Option<LoginIdentity> {
+        return match (&self.email, &self.phone) {
+            (Some(email), _) => Some(LoginIdentity::Email(email.clone())),
+            (None, Some(phone)) => Some(LoginIdentity::Phone(phone.clone())),
+            (None, None) => None,
+        };
+    }
+}
+
+/// Directory of the invoice templates and counter.
+pub fn default_config_dir() -> PathBuf {
+    let mut config_dir = home::home_dir().unwrap();
+    config_dir.push(".config/connectteam");
+    return config_dir;
+}
+
+pub fn default_defaults_path() -> PathBuf {
+    let mut defaults_file = home::home_dir().unwrap();
+    defaults_file.push(".config/connectteam.toml");
+    return defaults_file;
+}
+
+/// Reads the defaults file, a missing file means no defaults.
+pub fn load_defaults(defaults_file: &Path) -> Result<Defaults, ConnecteamError> {
+    if !defaults_file.exists() {
+        return Ok(Defaults::default());
+    }
+    let defaults_toml = std::fs::read_to_string(defaults_file)?;
+    return parse_defaults(&defaults_toml);
+}
+
+fn parse_defaults(defaults_toml: &str) -> Result<Defaults, ConnecteamError> {
+    return toml::from_str(defaults_toml).map_err(|err| ConnecteamError::Config(f!("invalid defaults file: {err}")));
+}
+
+pub async fn load_session_info_or_ask_user(client: &ApiClient, session_info_file: &Path, save: bool, container: &str) -> Result<SessionInfo, ConnecteamError> {
+    return match read_stored_session_info(session_info_file)? {
+        Some(info_json) => parse_session_info(&info_json),
+        None => login(client, session_info_file, save, container).await,
+    };
+}
+
+fn keyring_entry(session_info_file: &Path) -> Result<keyring::Entry, keyring::Error> {
+    // Keyed by the config path so sessions of different --config files stay apart.
+    return keyring::Entry::new(KEYRING_SERVICE, &session_info_file.to_string_lossy());
+}
+
+/// Reads the stored session from the OS keyring, or from the config file when the keyring has none or is not available.
+fn read_stored_session_info(session_info_file: &Path) -> Result<Option<String>, ConnecteamError> {
+    match keyring_entry(session_info_file).and_then(|entry| entry.get_password()) {
+        Ok(info_json) => return Ok(Some(info_json)),
+        Err(keyring::Error::NoEntry) => {}
+        Err(err) => log::info!("Keyring not available, reading {}: {}", session_info_file.display(), err),
+    }
+    if !session_info_file.exists() {
+        return Ok(None);
+    }
+    let info_json = std::fs::read_to_string(session_info_file)?;
+    let info: serde_json::Value = serde_json::from_str(&info_json)?;
+    return match info.get("encrypted") {
+        Some(encrypted) => Ok(Some(decrypt_session_info(encrypted, passphrase()?)?)),
+        None => Ok(Some(info_json)),
+    };
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedSessionInfo {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Asks for a passphrase twice and encrypts the config file with it from now on.
+pub fn ask_new_passphrase() -> Result<(), ConnecteamError> {
+    let passphrase = rpassword::prompt_password("New passphrase for the session file: ")?;
+    if passphrase.is_empty() || rpassword::prompt_password("Repeat the passphrase: ")? != passphrase {
+        return Err(ConnecteamError::Config("the passphrases are empty or don't match".to_string()));
+    }
+    let _ = PASSPHRASE.set(passphrase);
+    return Ok(());
+}
+
+/// Passphrase of the encrypted config file from CONNECTTEAM_PASSPHRASE, or asked for.
+fn passphrase() -> Result<&'static str, ConnecteamError> {
+    if let Some(passphrase) = PASSPHRASE.get() {
+        return Ok(passphrase);
+    }
+    let passphrase = match std::env::var("CONNECTTEAM_PASSPHRASE") {
+        Ok(passphrase) => passphrase,
+        Err(_) => rpassword::prompt_password("Passphrase of the session file: ")?,
+    };
+    return Ok(PASSPHRASE.get_or_init(|| passphrase));
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key, ConnecteamError> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| ConnecteamError::Config(f!("failed to derive the encryption key: {err}")))?;
+    return Ok(key);
+}
+
+fn encrypt_session_info(info_json: &str, passphrase: &str) -> Result<String, ConnecteamError> {
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, info_json.as_bytes())
+        .map_err(|_| ConnecteamError::Config("failed to encrypt the session".to_string()))?;
+
+    let encrypted = EncryptedSessionInfo {
+        salt: STANDARD.encode(salt),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    };
+    return Ok(serde_json::to_string_pretty(&serde_json::json!({ "encrypted": encrypted }))?);
+}
+
+fn decrypt_session_info(encrypted: &serde_json::Value, passphrase: &str) -> Result<String, ConnecteamError> {
+    let encrypted: EncryptedSessionInfo = serde_json::from_value(encrypted.clone())?;
+    let decode = |value: &str| STANDARD.decode(value).map_err(|err| ConnecteamError::Config(f!("invalid encrypted session: {err}")));
+    let salt = decode(&encrypted.salt)?;
+    let nonce = decode(&encrypted.nonce)?;
+    if nonce.len() != 12 {
+        return Err(ConnecteamError::Config("invalid encrypted session: bad nonce".to_string()));
+    }
+
+    let cipher = ChaCha20Poly1305::new(&derive_key(passphrase, &salt)?);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), decode(&encrypted.ciphertext)?.as_ref())
+        .map_err(|_| ConnecteamError::Config("wrong passphrase or damaged session file".to_string()))?;
+    return String::from_utf8(plaintext).map_err(|err| ConnecteamError::Config(err.to_string()));
+}
+
+/// Asks the user for session cookies until they pass a content structure request, then stores them unless `save` is false.
+pub async fn login(client: &ApiClient, session_info_file: &Path, save: bool, container: &str) -> Result<SessionInfo, ConnecteamError> {
+    loop {
+        let mut session_info = ask_user_for_session_info(session_info_file)?;
+        match get_object_ids_from_api(client, &session_info, container).await {
+            Ok(object_ids) => {
+                session_info.object_ids = object_ids;
+                if save {
+                    save_session_info(session_info_file, &session_info)?;
+                }
+                return Ok(session_info);
+            }
+            Err(ConnecteamError::Auth) => eprintln!("{}", ConnecteamError::Auth),
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Wrong codes accepted before giving up, Connecteam invalidates a code after a few tries anyway.
+const MAX_CODE_ATTEMPTS: u32 = 3;
+
+/// Logs in with a verification code sent by email or SMS instead of pasted cookies, then stores the session unless `save` is false.
+pub async fn login_with_code(client: &ApiClient, session_info_file: &Path, save: bool, container: &str, identity: &LoginIdentity) -> Result<SessionInfo, ConnecteamError> {
+    send_request_login_code(client, identity).await?;
+    let mut attempts = 0;
+    let cookies = loop {
+        eprintln!("Enter the verification code Connecteam sent you (leave empty to send a new one):");
+        let mut code = String::new();
+        if std::io::stdin().read_line(&mut code)? == 0 {
+            return Err(ConnecteamError::Auth);
+        }
+        if code.trim().is_empty() {
+            send_request_login_code(client, identity).await?;
+            continue;
+        }
+        match send_request_verify_code(client, identity, code.trim()).await {
+            Ok(cookies) => break cookies,
+            Err(ConnecteamError::Auth) if attempts + 1 < MAX_CODE_ATTEMPTS => {
+                attempts += 1;
+                eprintln!("The code was not accepted, please try again");
+            }
+            Err(err) => return Err(err),
+        }
+    };
+
+    let mut session_info = session_info_from_set_cookies(&cookies)?;
+    match identity {
+        LoginIdentity::Email(email) => session_info.email = Some(email.clone()),
+        LoginIdentity::Phone(phone) => session_info.phone = Some(phone.clone()),
+    }
+    session_info.object_ids = get_object_ids_from_api(client, &session_info, container).await?;
+    if save {
+        save_session_info(session_info_file, &session_info)?;
+    }
+    return Ok(session_info);
+}
+
+#[derive(clap::ValueEnum, Clone, PartialEq)]
+pub enum Browser {
+    Firefox,
+    Chrome,
+    Chromium,
+    Edge,
+    Brave,
+}
+
+/// Host the session cookies are set for.
+const COOKIE_DOMAIN: &str = "app.connecteam.com";
+
+/// Logs in with the session cookies of a browser that is logged in to Connecteam, then stores them unless `save` is false.
+pub async fn login_from_browser(client: &ApiClient, session_info_file: &Path, save: bool, container: &str, browser: &Browser) -> Result<SessionInfo, ConnecteamError> {
+    let domains = Some(vec![COOKIE_DOMAIN.to_string()]);
+    let cookies = match browser {
+        Browser::Firefox => rookie::firefox(domains),
+        Browser::Chrome => rookie::chrome(domains),
+        Browser::Chromium => rookie::chromium(domains),
+        Browser::Edge => rookie::edge(domains),
+        Browser::Brave => rookie::brave(domains),
+    }
+    .map_err(|err| ConnecteamError::Config(f!("failed to read the browser cookies: {err}")))?;
+
+    let cookies: Vec<String> = cookies.iter().map(|cookie| f!("{cookie.name}={cookie.value}")).collect();
+    let mut session_info = session_info_from_set_cookies(&cookies)
+        .map_err(|_| ConnecteamError::Config(f!("no Connecteam session in the browser, log in at https://{COOKIE_DOMAIN}/ first")))?;
+    session_info.object_ids = get_object_ids_from_api(client, &session_info, container).await?;
+    if save {
+        save_session_info(session_info_file, &session_info)?;
+    }
+    return Ok(session_info);
+}
+
+/// Picks the session cookies out of the `Set-Cookie` headers of a login response.
+fn session_info_from_set_cookies(set_cookies: &[String]) -> Result<SessionInfo, ConnecteamError> {
+    // Every header holds one cookie followed by its attributes, which the cookie lookup skips by name.
+    let cookies = set_cookies.join("; ");
+    return Ok(SessionInfo {
+        version: CONFIG_VERSION,
+        session: extract_field_from_cookie(&cookies, "session")?,
+        spirit: extract_field_from_cookie(&cookies, "_spirit")?,
+        ..Default::default()
+    });
+}
+
+/// Upgrades config files written by older versions to the current layout.
+fn migrate_session_info(mut info: serde_json::Value) -> serde_json::Value {
+    if !info.is_object() {
+        return info;
+    }
+    let version = info["version"].as_u64().unwrap_or(0);
+    if version > CONFIG_VERSION {
+        log::warn!("Config file version {} is newer than the supported version {}", version, CONFIG_VERSION);
+        return info;
+    }
+    // Version 0 files had no version field, their fields are a subset of version 1.
+    if version < 1 {
+        info["version"] = serde_json::json!(1);
+    }
+    return info;
+}
+
+fn parse_session_info(info_json: &str) -> Result<SessionInfo, ConnecteamError> {
+    let info: serde_json::Value = serde_json::from_str(info_json)?;
+    return Ok(serde_json::from_value(migrate_session_info(info))?);
+}
+
+fn ask_user_for_session_info(session_info_file: &Path) -> Result<SessionInfo, ConnecteamError> {
+    eprintln!("Valid session information is not stored in {}. Please go to https://app.connecteam.com/, login in, open developer console \
+    (ctrl+shift+c in most browsers), go to network, open time clock page in the browsers, navigate to Timesheet request, copy cookie values from request header, copy response to clipboard and past here:", session_info_file.to_str().unwrap());
+
+    let mut user_input = String::new();
+    let stdin = std::io::stdin();
+    stdin.read_line(&mut user_input)?;
+
+    let session_info = SessionInfo {
+        version: CONFIG_VERSION,
+        session: extract_field_from_cookie(&user_input, "session")?,
+        spirit: extract_field_from_cookie(&user_input, "_spirit")?,
+        ..Default::default()
+    };
+    return Ok(session_info);
+}
+
+fn strip_quotes(text: &str) -> &str {
+    return text.trim().trim_matches(|c: char| c == '\'' || c == '"').trim();
+}
+
+/// Finds a cookie value in a pasted cookie header, e.g. `Cookie: session=abc; _spirit=x==`.
+fn extract_field_from_cookie(cookie_header: &str, field: &str) -> Result<String, ConnecteamError> {
+    let mut cookies = strip_quotes(cookie_header);
+    if cookies.get(..7).map_or(false, |prefix| prefix.eq_ignore_ascii_case("cookie:")) {
+        cookies = &cookies[7..];
+    }
+
+    return cookies
+        .split(";")
+        .filter_map(|x| x.split_once("="))
+        .find(|(name, _)| name.trim() == field)
+        .map(|(_, value)| strip_quotes(value).to_string())
+        .filter(|value| !value.is_empty())
+        .ok_or_else(|| ConnecteamError::Config(f!("pasted cookies don't contain a value for {field}")));
+}
+
+/// Stores the session in the OS keyring, or in the config file when no keyring is available. With a passphrase, set by
+/// `login --encrypt` or when an encrypted file was read, the session always goes to the encrypted file.
+pub fn save_session_info(session_info_file: &Path, session_info: &SessionInfo) -> Result<(), ConnecteamError> {
+    let info_json = serde_json::to_string_pretty(session_info).unwrap();
+    let passphrase = PASSPHRASE.get().map(String::as_str);
+    match passphrase {
+        Some(_) => {
+            // A session left in the keyring would be read instead of the encrypted file.
+            match keyring_entry(session_info_file).and_then(|entry| entry.delete_credential()) {
+                Ok(()) | Err(keyring::Error::NoEntry) => {}
+                Err(err) => log::debug!("Could not remove the session from the keyring: {}", err),
+            }
+        }
+        None => match keyring_entry(session_info_file).and_then(|entry| entry.set_password(&info_json)) {
+            Ok(()) => {
+                // A plaintext copy left by an older version would be read by nobody but could still leak.
+                if session_info_file.exists() {
+                    std::fs::remove_file(session_info_file)?;
+                }
+                return Ok(());
+            }
+            Err(err) => log::warn!("Keyring not available, storing the session in {}: {}", session_info_file.display(), err),
+        },
+    }
+    return write_session_file(session_info_file, &info_json, passphrase);
+}
+
+/// Writes the session to the config file, encrypted when a passphrase is given.
+fn write_session_file(session_info_file: &Path, info_json: &str, passphrase: Option<&str>) -> Result<(), ConnecteamError> {
+    let contents = match passphrase {
+        Some(passphrase) => encrypt_session_info(info_json, passphrase)?,
+        None => info_json.to_string(),
+    };
+    if let Some(parent) = session_info_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(session_info_file, contents)?;
+    return Ok(());
+}
+
+pub async fn resolve_object_ids(client: &ApiClient, session_info_file: &Path, session_info: &mut SessionInfo, refresh: bool, save: bool, container: &str) -> Result<Vec<u64>, ConnecteamError> {
+    if !session_info.object_ids.is_empty() && !refresh {
+        log::debug!("Using stored object ids: {:?}", session_info.object_ids);
+        return Ok(session_info.object_ids.clone());
+    }
+    let object_ids = get_object_ids_from_api(client, session_info, container).await?;
+    session_info.object_ids = object_ids.clone();
+    if save {
+        save_session_info(session_info_file, session_info)?;
+    }
+    return Ok(object_ids);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_cookie_values() {
+        let header = "session=abc123; _spirit=xyz";
+        assert_eq!(extract_field_from_cookie(header, "session").unwrap(), "abc123");
+        assert_eq!(extract_field_from_cookie(header, "_spirit").unwrap(), "xyz");
+    }
+
+    #[test]
+    fn keeps_equal_signs_in_values() {
+        let header = "'Cookie: session=abc; _spirit=c3Bpcml0==; other=1'";
+        assert_eq!(extract_field_from_cookie(header, "session").unwrap(), "abc");
+        assert_eq!(extract_field_from_cookie(header, "_spirit").unwrap(), "c3Bpcml0==");
+    }
+
+    #[test]
+    fn handles_double_quotes() {
+        let header = "\"cookie: session=\"abc\"; _spirit=xyz\"";
+        assert_eq!(extract_field_from_cookie(header, "session").unwrap(), "abc");
+    }
+
+    #[test]
+    fn migrates_unversioned_config() {
+        let session_info = parse_session_info(r#"{"session": "abc", "spirit": "xyz"}"#).unwrap();
+        assert_eq!(session_info.version, CONFIG_VERSION);
+        assert_eq!(session_info.session, "abc");
+        assert!(session_info.object_ids.is_empty());
+    }
+
+    #[test]
+    fn parses_defaults() {
+        let defaults = parse_defaults("timezone = \"Europe/Warsaw\"\nformat = \"markdown\"\nwidth = 100\n").unwrap();
+        assert_eq!(defaults.timezone.as_deref(), Some("Europe/Warsaw"));
+        assert_eq!(
+            defaults.values(),
+            vec![
+                ("timezone", "Europe/Warsaw".to_string()),
+                ("format", "markdown".to_string()),
+                ("width", "100".to_string())
+            ]
+        );
+        assert_eq!(parse_defaults("").unwrap(), Defaults::default());
+        assert!(parse_defaults("colour = \"never\"").is_err());
+    }
+
+    #[test]
+    fn parses_contract() {
+        let defaults = parse_defaults("[contract]\nhours_per_week = 30\nworkdays = [\"mon\", \"tue\", \"wed\"]\n").unwrap();
+        let contract = defaults.contract.unwrap();
+        assert_eq!(contract.hours_on(chrono::Weekday::Tue).unwrap(), chrono::Duration::hours(10));
+        assert_eq!(contract.hours_on(chrono::Weekday::Fri).unwrap(), chrono::Duration::zero());
+        assert!(defaults.values().is_empty());
+
+        let contract = parse_defaults("[contract]\nhours_per_day = 7.5\n").unwrap().contract.unwrap();
+        assert_eq!(contract.hours_on(chrono::Weekday::Fri).unwrap(), chrono::Duration::minutes(450));
+        assert!(parse_defaults("[contract]\nworkdays = [\"mon\"]\n").unwrap().contract.unwrap().hours_on(chrono::Weekday::Mon).is_err());
+    }
+
+    #[test]
+    fn parses_rates() {
+        let defaults = parse_defaults("[rates]\nAcme = { rate = 80, currency = \"EUR\" }\n\"Acme / Support\" = { rate = 60.5 }\n").unwrap();
+        assert_eq!(defaults.rates["Acme"], ProjectRate { rate: 80.0, currency: Some("EUR".to_string()) });
+        assert_eq!(defaults.rates["Acme / Support"].rate, 60.5);
+        assert!(parse_defaults("[rates]\nAcme = { hourly = 80 }\n").is_err());
+    }
+
+    #[test]
+    fn parses_holidays() {
+        let defaults = parse_defaults("holidays = [\"2024-12-25\", \"2024-12-26\"]\n").unwrap();
+        assert_eq!(defaults.holidays, vec![chrono::NaiveDate::from_ymd_opt(2024, 12, 25).unwrap(), chrono::NaiveDate::from_ymd_opt(2024, 12, 26).unwrap()]);
+        assert_eq!(defaults.workdays().len(), 5);
+        assert!(parse_defaults("holidays = [\"christmas\"]\n").is_err());
+    }
+
+    #[test]
+    fn parses_anomaly_rules() {
+        let rules = parse_defaults("[anomalies]\nmax_shift_hours = 9\nweekends = true\nearliest_start = \"06:30\"\n").unwrap().anomalies;
+        assert_eq!(rules.max_shift_hours, 9.0);
+        assert!(rules.weekends);
+        assert_eq!(rules.earliest_start, chrono::NaiveTime::from_hms_opt(6, 30, 0));
+        assert_eq!(rules.latest_start, None);
+        assert_eq!(parse_defaults("").unwrap().anomalies, AnomalyRules::default());
+    }
+
+    #[test]
+    fn reads_session_from_set_cookie_headers() {
+        let set_cookies = vec![
+            "session=abc; Path=/; Secure; HttpOnly".to_string(),
+            "_spirit=c3Bpcml0==; Path=/; Max-Age=3600".to_string(),
+        ];
+        let session_info = session_info_from_set_cookies(&set_cookies).unwrap();
+        assert_eq!(session_info.session, "abc");
+        assert_eq!(session_info.spirit, "c3Bpcml0==");
+        assert!(session_info_from_set_cookies(&set_cookies[..1]).is_err());
+    }
+
+    #[test]
+    fn encrypts_session_with_passphrase() {
+        let info_json = r#"{"session": "abc", "spirit": "xyz"}"#;
+        let encrypted = encrypt_session_info(info_json, "correct horse").unwrap();
+        assert!(!encrypted.contains("abc"));
+
+        let encrypted: serde_json::Value = serde_json::from_str(&encrypted).unwrap();
+        assert_eq!(decrypt_session_info(&encrypted["encrypted"], "correct horse").unwrap(), info_json);
+        assert!(decrypt_session_info(&encrypted["encrypted"], "wrong horse").is_err());
+    }
+
+    #[test]
+    fn writes_encrypted_session_file_with_passphrase() {
+        let dir = std::env::temp_dir().join(format!("connectteam-session-test-{}", std::process::id()));
+        let path = dir.join("connectteam.json");
+        let info_json = r#"{"session": "abc", "spirit": "xyz"}"#;
+
+        write_session_file(&path, info_json, Some("correct horse")).unwrap();
+        let stored: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(decrypt_session_info(&stored["encrypted"], "correct horse").unwrap(), info_json);
+
+        write_session_file(&path, info_json, None).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), info_json);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reports_missing_cookie() {
+        assert!(extract_field_from_cookie("session=abc", "_spirit").is_err());
+    }
+}
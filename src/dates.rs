@@ -0,0 +1,147 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc, Weekday};
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq)]
+pub enum WeekStart {
+    Monday,
+    Sunday,
+}
+
+impl WeekStart {
+    fn weekday(&self) -> Weekday {
+        match self {
+            WeekStart::Monday => Weekday::Mon,
+            WeekStart::Sunday => Weekday::Sun,
+        }
+    }
+}
+
+pub fn start_of_week(date: NaiveDate, week_start: WeekStart) -> NaiveDate {
+    let days_since_start = (date.weekday().num_days_from_monday() + 7 - week_start.weekday().num_days_from_monday()) % 7;
+    return date - Duration::days(days_since_start as i64);
+}
+
+pub fn start_of_month(date: NaiveDate) -> NaiveDate {
+    return NaiveDate::from_ymd_opt(date.year(), date.month(), 1).unwrap();
+}
+
+pub fn end_of_month(date: NaiveDate) -> NaiveDate {
+    let next_month = start_of_month(date) + Duration::days(32);
+    return start_of_month(next_month) - Duration::days(1);
+}
+
+pub fn this_week(today: NaiveDate, week_start: WeekStart) -> (NaiveDate, NaiveDate) {
+    let start = start_of_week(today, week_start);
+    return (start, start + Duration::days(6));
+}
+
+pub fn last_week(today: NaiveDate, week_start: WeekStart) -> (NaiveDate, NaiveDate) {
+    return this_week(today - Duration::days(7), week_start);
+}
+
+/// Parses a `YYYY-MM` month into its first day, used as a clap value parser.
+pub fn parse_month(month: &str) -> Result<NaiveDate, String> {
+    return NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").map_err(|_| f!("expected a month like 2024-03, got {month}"));
+}
+
+pub fn this_month(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    return (start_of_month(today), end_of_month(today));
+}
+
+pub fn last_month(today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    return this_month(start_of_month(today) - Duration::days(1));
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, PartialEq)]
+pub enum RoundMode {
+    Nearest,
+    Up,
+    Down,
+}
+
+/// Rounds a punch timestamp to a multiple of `minutes`.
+pub fn round_time(time: DateTime<Utc>, minutes: u32, mode: RoundMode) -> DateTime<Utc> {
+    let step = minutes as i64 * 60;
+    let seconds = time.timestamp();
+    let down = seconds.div_euclid(step) * step;
+    let rounded = match mode {
+        RoundMode::Down => down,
+        RoundMode::Up if down == seconds => down,
+        RoundMode::Up => down + step,
+        // Halfway between two steps rounds down, like 9:07:30 to 9:00 with 15 minutes.
+        RoundMode::Nearest if seconds - down <= step / 2 => down,
+        RoundMode::Nearest => down + step,
+    };
+    return Utc.timestamp_opt(rounded, 0).unwrap();
+}
+
+/// Longest range the timesheet endpoint returns complete results for.
+const MAX_REQUEST_DAYS: i64 = 31;
+
+/// Splits ranges longer than the API handles into calendar month chunks.
+pub fn split_into_months(start: NaiveDate, end: NaiveDate) -> Vec<(NaiveDate, NaiveDate)> {
+    if (end - start).num_days() <= MAX_REQUEST_DAYS {
+        return vec![(start, end)];
+    }
+    let mut chunks = vec![];
+    let mut chunk_start = start;
+    while chunk_start <= end {
+        let chunk_end = end_of_month(chunk_start).min(end);
+        chunks.push((chunk_start, chunk_end));
+        chunk_start = chunk_end + Duration::days(1);
+    }
+    return chunks;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        return NaiveDate::from_ymd_opt(year, month, day).unwrap();
+    }
+
+    #[test]
+    fn week_ranges_respect_week_start() {
+        // 2024-03-13 is a Wednesday
+        assert_eq!(this_week(date(2024, 3, 13), WeekStart::Monday), (date(2024, 3, 11), date(2024, 3, 17)));
+        assert_eq!(this_week(date(2024, 3, 13), WeekStart::Sunday), (date(2024, 3, 10), date(2024, 3, 16)));
+        assert_eq!(last_week(date(2024, 3, 11), WeekStart::Monday), (date(2024, 3, 4), date(2024, 3, 10)));
+    }
+
+    #[test]
+    fn parses_months() {
+        assert_eq!(parse_month("2024-03"), Ok(date(2024, 3, 1)));
+        assert!(parse_month("2024-13").is_err());
+        assert!(parse_month("march").is_err());
+    }
+
+    #[test]
+    fn month_ranges_cover_whole_months() {
+        assert_eq!(this_month(date(2024, 2, 10)), (date(2024, 2, 1), date(2024, 2, 29)));
+        assert_eq!(last_month(date(2024, 1, 15)), (date(2023, 12, 1), date(2023, 12, 31)));
+    }
+
+    #[test]
+    fn rounds_times_to_increments() {
+        let time = Utc.with_ymd_and_hms(2024, 3, 13, 9, 7, 30).unwrap();
+        let at = |hour, minute| Utc.with_ymd_and_hms(2024, 3, 13, hour, minute, 0).unwrap();
+        assert_eq!(round_time(time, 15, RoundMode::Nearest), at(9, 0));
+        assert_eq!(round_time(time, 15, RoundMode::Up), at(9, 15));
+        assert_eq!(round_time(time, 15, RoundMode::Down), at(9, 0));
+        assert_eq!(round_time(at(9, 8), 15, RoundMode::Nearest), at(9, 15));
+        assert_eq!(round_time(at(9, 30), 15, RoundMode::Up), at(9, 30));
+    }
+
+    #[test]
+    fn splits_long_ranges_by_month() {
+        assert_eq!(split_into_months(date(2024, 3, 1), date(2024, 3, 31)), vec![(date(2024, 3, 1), date(2024, 3, 31))]);
+        assert_eq!(
+            split_into_months(date(2024, 1, 15), date(2024, 3, 10)),
+            vec![
+                (date(2024, 1, 15), date(2024, 1, 31)),
+                (date(2024, 2, 1), date(2024, 2, 29)),
+                (date(2024, 3, 1), date(2024, 3, 10)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+
+use crate::config::ProjectRate;
+use crate::parse::TimesheetEntry;
+
+/// Worked time of a project and subproject with the rate it is billed at, `None` when no rate is configured.
+#[derive(Debug, PartialEq)]
+pub struct Earning {
+    pub project: String,
+    pub subproject: String,
+    pub duration: chrono::Duration,
+    pub rate: Option<ProjectRate>,
+}
+
+impl Earning {
+    pub fn amount(&self) -> Option<f64> {
+        return self.rate.as_ref().map(|rate| rate.rate * self.duration.num_minutes() as f64 / 60.0);
+    }
+}
+
+/// The rate of "project / subproject" wins over the rate of the project.
+pub fn rate_for<'a>(rates: &'a BTreeMap<String, ProjectRate>, project: &str, subproject: &str) -> Option<&'a ProjectRate> {
+    if !subproject.is_empty() {
+        if let Some(rate) = rates.get(&format!("{} / {}", project, subproject)) {
+            return Some(rate);
+        }
+    }
+    return rates.get(project);
+}
+
+pub fn compute_earnings(entries: &[TimesheetEntry], rates: &BTreeMap<String, ProjectRate>) -> Vec<Earning> {
+    let mut totals: BTreeMap<(String, String), chrono::Duration> = BTreeMap::new();
+    for entry in entries {
+        let total = totals.entry((entry.project.clone(), entry.subproject.clone())).or_insert(chrono::Duration::zero());
+        *total = *total + entry.net_duration();
+    }
+    return totals
+        .into_iter()
+        .map(|((project, subproject), duration)| Earning {
+            rate: rate_for(rates, &project, &subproject).cloned(),
+            project: project,
+            subproject: subproject,
+            duration: duration,
+        })
+        .collect();
+}
+
+/// Sum of the amounts per currency, amounts without a currency are keyed by an empty string.
+pub fn totals_by_currency(earnings: &[Earning]) -> BTreeMap<String, f64> {
+    let mut totals = BTreeMap::new();
+    for earning in earnings {
+        let (Some(rate), Some(amount)) = (&earning.rate, earning.amount()) else { continue };
+        *totals.entry(rate.currency.clone().unwrap_or_default()).or_insert(0.0) += amount;
+    }
+    return totals;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn shift(project: &str, subproject: &str, hours: i64) -> TimesheetEntry {
+        let start = Utc.with_ymd_and_hms(2023, 10, 2, 8, 0, 0).unwrap();
+        return TimesheetEntry::test_shift(start, Some(start + chrono::Duration::hours(hours))).on_project(project, subproject);
+    }
+
+    fn rate(rate: f64, currency: &str) -> ProjectRate {
+        return ProjectRate { rate: rate, currency: Some(currency.to_string()) };
+    }
+
+    #[test]
+    fn multiplies_hours_by_rates() {
+        let rates = BTreeMap::from([
+            ("Acme".to_string(), rate(80.0, "EUR")),
+            ("Acme / Support".to_string(), rate(60.0, "EUR")),
+            ("Globex".to_string(), rate(100.0, "USD")),
+        ]);
+        let entries = vec![shift("Acme", "Backend", 2), shift("Acme", "Support", 3), shift("Acme", "Backend", 1), shift("Globex", "", 1), shift("Initech", "", 4)];
+        let earnings = compute_earnings(&entries, &rates);
+
+        let amounts: Vec<Option<f64>> = earnings.iter().map(|earning| earning.amount()).collect();
+        assert_eq!(amounts, vec![Some(240.0), Some(180.0), Some(100.0), None]);
+        assert_eq!(totals_by_currency(&earnings), BTreeMap::from([("EUR".to_string(), 420.0), ("USD".to_string(), 100.0)]));
+    }
+}
@@ -0,0 +1,133 @@
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use chrono_tz::Tz;
+
+use crate::parse::TimesheetEntry;
+use crate::render::shift_project;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// The editable values of a finished shift, times in the display timezone.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ShiftFields {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// "Project / Subproject" like the tables show it, resolved like `--tag` when submitting.
+    pub tag: String,
+    /// The employee notes only, the attachment texts shown in `desc` can't be changed here.
+    pub note: String,
+}
+
+impl ShiftFields {
+    /// `None` for running shifts, they have no end to edit yet.
+    pub fn from_entry(entry: &TimesheetEntry, timezone: &Tz) -> Option<ShiftFields> {
+        return Some(ShiftFields {
+            start: entry.start.with_timezone(timezone).naive_local(),
+            end: entry.end?.with_timezone(timezone).naive_local(),
+            tag: shift_project(entry),
+            note: entry.notes.clone(),
+        });
+    }
+
+    pub fn to_text(&self) -> String {
+        return format!(
+            "# Change the values and save, lines starting with # are ignored.\nstart = {}\nend = {}\ntag = {}\nnote = {}\n",
+            self.start.format(TIME_FORMAT),
+            self.end.format(TIME_FORMAT),
+            self.tag,
+            self.note
+        );
+    }
+
+    /// Reads back the text of `to_text`, every value has to be there.
+    pub fn parse(text: &str) -> Result<ShiftFields> {
+        let (mut start, mut end, mut tag, mut note) = (None, None, None, None);
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let (key, value) = line.split_once('=').with_context(|| format!("Expected \"name = value\", got {:?}", line))?;
+            let value = value.trim().to_string();
+            match key.trim() {
+                "start" => start = Some(NaiveDateTime::parse_from_str(&value, TIME_FORMAT).with_context(|| format!("Invalid start {:?}", value))?),
+                "end" => end = Some(NaiveDateTime::parse_from_str(&value, TIME_FORMAT).with_context(|| format!("Invalid end {:?}", value))?),
+                "tag" => tag = Some(value),
+                "note" => note = Some(value),
+                other => anyhow::bail!("Unknown value {:?}, expected start, end, tag or note", other),
+            }
+        }
+        return Ok(ShiftFields {
+            start: start.context("The start is missing")?,
+            end: end.context("The end is missing")?,
+            tag: tag.context("The tag is missing")?,
+            note: note.unwrap_or_default(),
+        });
+    }
+
+    /// Name, old and new value of every changed field.
+    pub fn changes(&self, edited: &ShiftFields) -> Vec<(&'static str, String, String)> {
+        let fields = [
+            ("start", self.start.format(TIME_FORMAT).to_string(), edited.start.format(TIME_FORMAT).to_string()),
+            ("end", self.end.format(TIME_FORMAT).to_string(), edited.end.format(TIME_FORMAT).to_string()),
+            ("tag", self.tag.clone(), edited.tag.clone()),
+            ("note", self.note.clone(), edited.note.clone()),
+        ];
+        return fields.into_iter().filter(|(_, old, new)| old != new).collect();
+    }
+}
+
+/// Opens the fields in $VISUAL or $EDITOR (vi without either) and reads back what was saved.
+pub fn edit_in_editor(fields: &ShiftFields) -> Result<ShiftFields> {
+    let path = std::env::temp_dir().join(format!("connectteam-shift-{}.txt", std::process::id()));
+    std::fs::write(&path, fields.to_text()).with_context(|| format!("Failed to write {:?}", path))?;
+
+    let editor = std::env::var("VISUAL").or_else(|_| std::env::var("EDITOR")).unwrap_or_else(|_| "vi".to_string());
+    // Editors are often given with arguments, e.g. "code --wait".
+    let mut words = editor.split_whitespace();
+    let program = words.next().context("$EDITOR is empty")?;
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to start the editor {:?}", editor))?;
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+    if !status.success() {
+        anyhow::bail!("The editor exited with {}, the shift is left as it is", status);
+    }
+    return ShiftFields::parse(&edited?);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> ShiftFields {
+        let time = |hour: u32| chrono::NaiveDate::from_ymd_opt(2024, 5, 2).unwrap().and_hms_opt(hour, 0, 0).unwrap();
+        return ShiftFields { start: time(9), end: time(17), tag: "Acme / Backend".to_string(), note: "Release".to_string() };
+    }
+
+    #[test]
+    fn edits_notes_without_attachment_texts() {
+        let entries = crate::parse::parse_timesheet(include_str!("../tests/fixtures/timesheet.json").to_string()).unwrap();
+        // The first shift has the attachment text "Reviewed PR #42" and the notes "pairing session".
+        let fields = ShiftFields::from_entry(&entries[0], &chrono_tz::UTC).unwrap();
+        assert_eq!(fields.note, "pairing session");
+        assert_eq!(fields.tag, "Acme / Backend");
+
+        let open = crate::parse::parse_timesheet(include_str!("../tests/fixtures/timesheet_open_shift.json").to_string()).unwrap();
+        assert!(ShiftFields::from_entry(&open[0], &chrono_tz::UTC).is_none());
+    }
+
+    #[test]
+    fn reads_back_edited_text() {
+        let original = fields();
+        let text = original.to_text().replace("17:00", "17:30").replace("note = Release", "note = Release 1.2");
+        let edited = ShiftFields::parse(&text).unwrap();
+        assert_eq!(edited.tag, "Acme / Backend");
+
+        let changes = original.changes(&edited);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0], ("end", "2024-05-02 17:00".to_string(), "2024-05-02 17:30".to_string()));
+        assert_eq!(changes[1].0, "note");
+        assert!(ShiftFields::parse("start = 2024-05-02 09:00\n").is_err());
+        assert!(ShiftFields::parse(&format!("{}colour = red\n", original.to_text())).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+#[derive(Debug)]
+pub enum ConnecteamError {
+    Auth,
+    Network(reqwest::Error),
+    Parse(String),
+    Config(String),
+    NoObjectId,
+    UnknownTag(String),
+    AmbiguousTag(String),
+    UnknownContainer(String),
+}
+
+impl ConnecteamError {
+    /// Process exit code for this error: 2 auth, 3 network, 4 parse, 5 config.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ConnecteamError::Auth => 2,
+            ConnecteamError::Network(_) => 3,
+            ConnecteamError::Parse(_) => 4,
+            ConnecteamError::Config(_) | ConnecteamError::NoObjectId | ConnecteamError::UnknownContainer(_) => 5,
+            ConnecteamError::UnknownTag(_) | ConnecteamError::AmbiguousTag(_) => 1,
+        }
+    }
+}
+
+impl std::fmt::Display for ConnecteamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConnecteamError::Auth => write!(f, "Session expired or invalid, please re-authenticate"),
+            ConnecteamError::Network(err) => write!(f, "Request failed: {}", err),
+            ConnecteamError::Parse(msg) => write!(f, "Failed to parse response: {}", msg),
+            ConnecteamError::Config(msg) => write!(f, "Invalid session config: {}", msg),
+            ConnecteamError::NoObjectId => write!(f, "No punchclock object id found in content structure"),
+            ConnecteamError::UnknownTag(msg) => write!(f, "Unknown project or subproject: {}", msg),
+            ConnecteamError::AmbiguousTag(msg) => write!(f, "Ambiguous project or subproject: {}", msg),
+            ConnecteamError::UnknownContainer(msg) => write!(f, "No container named {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConnecteamError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConnecteamError::Network(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ConnecteamError {
+    fn from(err: reqwest::Error) -> Self {
+        ConnecteamError::Network(err)
+    }
+}
+
+impl From<std::io::Error> for ConnecteamError {
+    fn from(err: std::io::Error) -> Self {
+        ConnecteamError::Config(err.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for ConnecteamError {
+    fn from(err: rusqlite::Error) -> Self {
+        ConnecteamError::Config(format!("local mirror: {}", err))
+    }
+}
+
+impl From<serde_json::Error> for ConnecteamError {
+    fn from(err: serde_json::Error) -> Self {
+        ConnecteamError::Config(err.to_string())
+    }
+}
@@ -0,0 +1,234 @@
+use chrono::NaiveDate;
+use std::path::{Path, PathBuf};
+
+use crate::earnings::Earning;
+use crate::render::{escape_html, format_duration};
+
+/// Used when the config directory has no `invoice.txt`.
+const DEFAULT_TEXT_TEMPLATE: &str = "INVOICE {{number}}
+Date: {{date}}
+
+From:
+{{issuer}}
+
+To:
+{{client}}
+
+Period: {{period}}
+
+{{items}}
+
+Total: {{total}}
+";
+
+/// Used when the config directory has no `invoice.html`.
+const DEFAULT_HTML_TEMPLATE: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Invoice {{number}}</title>
+<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+th { background: #eee; }
+td.amount { text-align: right; }
+</style>
+</head>
+<body>
+<h1>Invoice {{number}}</h1>
+<p>Date: {{date}}<br>Period: {{period}}</p>
+<p><strong>From</strong><br>{{issuer}}</p>
+<p><strong>To</strong><br>{{client}}</p>
+{{items}}
+<p><strong>Total: {{total}}</strong></p>
+</body>
+</html>
+";
+
+pub struct Invoice {
+    pub number: String,
+    pub date: NaiveDate,
+    pub client: String,
+    pub issuer: String,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// Line items, all of them have a rate in `currency`.
+    pub items: Vec<Earning>,
+    pub currency: String,
+}
+
+impl Invoice {
+    pub fn total(&self) -> f64 {
+        return self.items.iter().filter_map(|item| item.amount()).sum();
+    }
+
+    pub fn period(&self) -> String {
+        return format!("{} - {}", self.start.format("%Y-%m-%d"), self.end.format("%Y-%m-%d"));
+    }
+
+    fn money(&self, amount: f64) -> String {
+        return format!("{:.2} {}", amount, self.currency).trim_end().to_string();
+    }
+}
+
+pub fn item_label(item: &Earning) -> String {
+    return match item.subproject.as_str() {
+        "" => item.project.clone(),
+        subproject => format!("{} / {}", item.project, subproject),
+    };
+}
+
+/// Replaces the `{{name}}` placeholders, unknown placeholders are left as they are.
+fn fill_template(template: &str, values: &[(&str, String)]) -> String {
+    let mut filled = template.to_string();
+    for (name, value) in values {
+        filled = filled.replace(&format!("{{{{{}}}}}", name), value);
+    }
+    return filled;
+}
+
+/// The template of the config directory, or the built-in one.
+fn load_template(config_dir: &Path, file_name: &str, default: &str) -> std::io::Result<String> {
+    let path = config_dir.join(file_name);
+    if !path.exists() {
+        return Ok(default.to_string());
+    }
+    return std::fs::read_to_string(path);
+}
+
+pub fn render_text_invoice(invoice: &Invoice, config_dir: &Path) -> std::io::Result<String> {
+    let width = invoice.items.iter().map(|item| item_label(item).chars().count()).max().unwrap_or(0).max("Project".len());
+    let mut lines = vec![format!("{:width$}  {:>8}  {:>12}  {:>12}", "Project", "Hours", "Rate", "Amount", width = width)];
+    for item in &invoice.items {
+        lines.push(format!(
+            "{:width$}  {:>8}  {:>12}  {:>12}",
+            item_label(item),
+            format_duration(item.duration),
+            invoice.money(item.rate.as_ref().map(|rate| rate.rate).unwrap_or_default()),
+            invoice.money(item.amount().unwrap_or_default()),
+            width = width
+        ));
+    }
+    let template = load_template(config_dir, "invoice.txt", DEFAULT_TEXT_TEMPLATE)?;
+    return Ok(fill_template(&template, &template_values(invoice, lines.join("\n"), invoice.issuer.clone(), invoice.client.clone())));
+}
+
+pub fn render_html_invoice(invoice: &Invoice, config_dir: &Path) -> std::io::Result<String> {
+    let mut rows = vec!["<tr><th>Project</th><th>Hours</th><th>Rate</th><th>Amount</th></tr>".to_string()];
+    for item in &invoice.items {
+        rows.push(format!(
+            "<tr><td>{}</td><td class=\"amount\">{}</td><td class=\"amount\">{}</td><td class=\"amount\">{}</td></tr>",
+            escape_html(&item_label(item)),
+            format_duration(item.duration),
+            escape_html(&invoice.money(item.rate.as_ref().map(|rate| rate.rate).unwrap_or_default())),
+            escape_html(&invoice.money(item.amount().unwrap_or_default()))
+        ));
+    }
+    let items = format!("<table>\n{}\n</table>", rows.join("\n"));
+    let issuer = escape_html(&invoice.issuer).replace('\n', "<br>");
+    let client = escape_html(&invoice.client).replace('\n', "<br>");
+    let template = load_template(config_dir, "invoice.html", DEFAULT_HTML_TEMPLATE)?;
+    return Ok(fill_template(&template, &template_values(invoice, items, issuer, client)));
+}
+
+fn template_values(invoice: &Invoice, items: String, issuer: String, client: String) -> Vec<(&'static str, String)> {
+    return vec![
+        ("number", invoice.number.clone()),
+        ("date", invoice.date.format("%Y-%m-%d").to_string()),
+        ("period", invoice.period()),
+        ("issuer", issuer),
+        ("client", client),
+        ("items", items),
+        ("total", invoice.money(invoice.total())),
+    ];
+}
+
+/// File holding the last issued invoice number, next to the templates.
+pub fn invoice_counter_path(config_dir: &Path) -> PathBuf {
+    return config_dir.join("invoice-number");
+}
+
+/// Number the next invoice gets, one more than the last issued one.
+pub fn next_invoice_number(counter_path: &Path) -> std::io::Result<u32> {
+    if !counter_path.exists() {
+        return Ok(1);
+    }
+    let last = std::fs::read_to_string(counter_path)?;
+    let last: u32 = last
+        .trim()
+        .parse()
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{:?} is not a number: {}", counter_path, err)))?;
+    return Ok(last + 1);
+}
+
+/// Remembers `number` as the last issued invoice number, creating the config directory if needed.
+pub fn save_invoice_number(counter_path: &Path, number: u32) -> std::io::Result<()> {
+    if let Some(dir) = counter_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    return std::fs::write(counter_path, number.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fills_placeholders() {
+        let filled = fill_template("Invoice {{number}} for {{client}} {{unknown}}", &[("number", "INV-7".to_string()), ("client", "Acme".to_string())]);
+        assert_eq!(filled, "Invoice INV-7 for Acme {{unknown}}");
+    }
+
+    fn invoice() -> Invoice {
+        let date = |day: u32| NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+        return Invoice {
+            number: "INV-0007".to_string(),
+            date: date(31),
+            client: "Acme Ltd\n1 Main St".to_string(),
+            issuer: "Jane Doe".to_string(),
+            start: date(1),
+            end: date(31),
+            items: vec![Earning {
+                project: "R&D".to_string(),
+                subproject: "Backend".to_string(),
+                duration: chrono::Duration::minutes(90),
+                rate: Some(crate::config::ProjectRate { rate: 80.0, currency: Some("EUR".to_string()) }),
+            }],
+            currency: "EUR".to_string(),
+        };
+    }
+
+    #[test]
+    fn counts_invoice_numbers_up() {
+        let dir = std::env::temp_dir().join(format!("connectteam-invoice-test-{}", std::process::id()));
+        let path = invoice_counter_path(&dir);
+        assert_eq!(next_invoice_number(&path).unwrap(), 1);
+
+        // The config directory is created with the first saved number.
+        save_invoice_number(&path, 1).unwrap();
+        assert_eq!(next_invoice_number(&path).unwrap(), 2);
+        save_invoice_number(&path, 2).unwrap();
+        assert_eq!(next_invoice_number(&path).unwrap(), 3);
+
+        std::fs::write(&path, "seven").unwrap();
+        assert_eq!(next_invoice_number(&path).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn renders_builtin_templates() {
+        // Without templates in the config directory the built-in ones are used.
+        let dir = std::env::temp_dir().join(format!("connectteam-invoice-templates-test-{}", std::process::id()));
+        let text = render_text_invoice(&invoice(), &dir).unwrap();
+        assert!(text.starts_with("INVOICE INV-0007\nDate: 2024-05-31\n"));
+        assert!(text.contains("Period: 2024-05-01 - 2024-05-31"));
+        assert!(text.contains("Project           Hours          Rate        Amount\nR&D / Backend      1:30     80.00 EUR    120.00 EUR"));
+        assert!(text.ends_with("Total: 120.00 EUR\n"));
+
+        let html = render_html_invoice(&invoice(), &dir).unwrap();
+        assert!(html.contains("<td>R&amp;D / Backend</td><td class=\"amount\">1:30</td>"));
+        assert!(html.contains("<strong>To</strong><br>Acme Ltd<br>1 Main St"));
+        assert!(html.contains("<p><strong>Total: 120.00 EUR</strong></p>"));
+    }
+}
@@ -0,0 +1,11 @@
+use serde::de::DeserializeOwned;
+
+use crate::error::ConnecteamError;
+
+/// Parses an API response into its model, naming the endpoint and showing the start of the body when it doesn't match.
+pub fn parse_response<T: DeserializeOwned>(resp: &str, endpoint: &str) -> Result<T, ConnecteamError> {
+    return serde_json::from_str(resp).map_err(|err| {
+        let body_start: String = resp.chars().take(200).collect();
+        ConnecteamError::Parse(f!("{endpoint} returned unexpected JSON ({err}), response starts with: {body_start}"))
+    });
+}
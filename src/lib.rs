@@ -0,0 +1,21 @@
+//! Client for the Connecteam punch clock API used by the `connectteam_cli` binary.
+
+#[macro_use]
+extern crate fstrings;
+
+pub mod api;
+pub mod cache;
+pub mod checks;
+pub mod client;
+pub mod config;
+pub mod dates;
+pub mod earnings;
+pub mod error;
+pub mod json_ext;
+pub mod models;
+pub mod overtime;
+pub mod parse;
+pub mod stats;
+pub mod store;
+
+pub use client::ConnecteamClient;
@@ -5,297 +5,1234 @@ extern crate fstrings;
 extern crate chrono_english;
 extern crate chrono;
 
-use clap::Parser;
+mod cli;
+mod edit;
+mod invoice;
+mod pdf;
+mod render;
+mod tui;
+mod xlsx;
+
 use chrono_english::{parse_date_string,Dialect};
 use chrono::prelude::*;
-use chrono::{DateTime, Datelike, TimeZone, Utc};
-use json::JsonValue::{self, Array, Number};
-use serde::{Deserialize, Serialize};
-use serde_json::json;
-use term_table::row::Row;
-use term_table::table_cell::TableCell;
+use chrono_tz::Tz;
 use anyhow::{Result, Context};
+use colored::Colorize;
+use futures::future::try_join_all;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+use connectteam_cli::{api, cache, checks, config, dates, earnings, error, overtime, parse, stats, store, ConnecteamClient};
 
-struct TimesheetEntry {
-    start: DateTime<Utc>,
-    end: DateTime<Utc>,
-    desc: String,
-    project: String,
-    subproject: String,
+use api::{ApiClient, LoginIdentity, NewShift};
+use cache::{default_cache_dir, default_clock_state_path, load_clock_state, save_clock_state, CacheMode, ClockState, ResponseCache};
+use cli::{AddArgs, CheckAction, Cli, ColorChoice, Command, EarningsArgs, ExportArgs, ExportKind, FilterArgs, InvoiceArgs, InvoiceFormat, LoginArgs, NoteArgs, OutputFormat, OvertimeArgs, PunchAction, PunchInArgs, PunchOutArgs, RangeArgs, ReportArgs, ReportKind, ShiftAction, ShiftAddArgs, ShiftDeleteArgs, ShiftEditArgs, StatsArgs, StatusArgs, StatusFormat, SummaryArgs, SyncArgs, TagsFormat, TimesheetArgs, TuiArgs};
+use config::{default_session_info_path, AnomalyRules, Contract, Defaults, ProjectRate, load_session_info_or_ask_user, login, login_from_browser, login_with_code, resolve_object_ids, SessionInfo};
+use error::ConnecteamError;
+use store::{default_store_path, Store};
+use parse::{find_tag_ids, parse_timesheet, resolve_tag, PunchclockMeta, Tag, TimesheetEntry};
+use render::{describe_shift, elapsed, export_ics, format_duration, render_anomalies, render_csv, render_earnings, render_gaps, render_html, render_html_report, render_markdown, render_overlaps, render_overtime, render_period_summary, render_prompt_status, render_stats, render_status_line, render_summary, render_tags, render_timesheet, render_waybar_status, shift_project, weekly_totals, Rate, SortOrder};
+
+fn matches_filter(value: &str, filter: &Option<String>) -> bool {
+    match filter {
+        Some(filter) => value.to_lowercase().contains(&filter.to_lowercase()),
+        None => true,
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct SessionInfo {
-    session: String,
-    spirit: String,
+fn apply_filters(entries: &mut Vec<TimesheetEntry>, filter: &FilterArgs) {
+    entries.retain(|entry| {
+        matches_filter(&entry.project, &filter.project)
+            && matches_filter(&entry.subproject, &filter.subproject)
+            && filter.grep.as_ref().is_none_or(|pattern| pattern.is_match(&entry.desc))
+    });
 }
 
-#[derive(Serialize, Deserialize)]
-struct TimesheetParams {
-    #[serde(rename = "startDate")]
-    start_date: String,
+fn resolve_timezone(timezone: &Option<String>) -> Result<Tz> {
+    let name = match timezone {
+        Some(name) => name.clone(),
+        None => iana_time_zone::get_timezone().context("Failed to detect system timezone")?,
+    };
+    return name.parse::<Tz>().map_err(|e| anyhow::anyhow!(f!("Unknown timezone {name}: {e}")));
+}
 
-    #[serde(rename = "endDate")]
-    end_date: String,
+/// Runs `request` with the stored session and punchclock object ids, logging in again once if the session has expired.
+async fn with_session<T>(
+    args: &Cli,
+    client: &ApiClient,
+    config_path: &Path,
+    request: impl AsyncFn(&ConnecteamClient, Vec<u64>) -> Result<T, ConnecteamError>,
+) -> Result<T, ConnecteamError> {
+    // Nothing is stored or asked for when authenticating with a token.
+    let save = !args.no_save && args.token.is_none();
+    let mut session_info = match &args.token {
+        Some(token) => SessionInfo { token: Some(token.clone()), ..Default::default() },
+        None => load_session_info_or_ask_user(client, config_path, save, &args.container).await?,
+    };
 
-    #[serde(rename = "objectId")]
-    object_id: u64,
+    let run = async |session_info: &mut SessionInfo| -> Result<T, ConnecteamError> {
+        let object_ids = match args.object_id {
+            Some(object_id) => vec![object_id],
+            None => resolve_object_ids(client, config_path, session_info, args.refresh_object_id, save, &args.container).await?,
+        };
+        return request(&ConnecteamClient::new(client.clone(), session_info.clone()), object_ids).await;
+    };
 
-    #[serde(rename = "defaultTimezone")]
-    default_timezone: String,
+    return match run(&mut session_info).await {
+        Err(ConnecteamError::Auth) if args.token.is_none() => {
+            eprintln!("{}", ConnecteamError::Auth);
+            // Sessions created with a verification code get a new code instead of asking for cookies.
+            session_info = match session_info.login_identity() {
+                Some(identity) => login_with_code(client, config_path, save, &args.container, &identity).await?,
+                None => login(client, config_path, save, &args.container).await?,
+            };
+            run(&mut session_info).await
+        }
+        result => result,
+    };
+}
 
-    #[serde(rename = "_spirit")]
-    _spirit: String,
+async fn fetch_timesheets(
+    args: &Cli,
+    user: Option<u64>,
+    client: &ApiClient,
+    config_path: &Path,
+    start: &NaiveDate,
+    end: &NaiveDate,
+    timezone: &Tz,
+) -> Result<Vec<(u64, String)>> {
+    let resps: Result<Vec<(u64, String)>, ConnecteamError> = with_session(args, client, config_path, async |connecteam, object_ids| {
+        let ranges: Vec<(u64, NaiveDate)> = object_ids.iter().map(|object_id| (*object_id, *start)).collect();
+        return fetch_ranges(connecteam, user, &ranges, end, timezone).await;
+    })
+    .await;
+    return resps.with_context(|| "Failed to send request \"get timesheet\"");
 }
 
-#[derive(Serialize, Deserialize)]
-struct PunchclockDataParams {
-    #[serde(rename = "objectId")]
-    object_id: u64,
+/// Timesheet responses of each object id from its own start day to `end`, tagged with the object id.
+async fn fetch_ranges(
+    connecteam: &ConnecteamClient,
+    user: Option<u64>,
+    ranges: &[(u64, NaiveDate)],
+    end: &NaiveDate,
+    timezone: &Tz,
+) -> Result<Vec<(u64, String)>, ConnecteamError> {
+    let requests: Vec<(u64, NaiveDate, NaiveDate)> = ranges
+        .iter()
+        .flat_map(|(object_id, start)| dates::split_into_months(*start, *end).into_iter().map(move |(start, end)| (*object_id, start, end)))
+        .collect();
+    if requests.len() > ranges.len() {
+        log::info!("Splitting range into {} monthly requests", requests.len());
+    }
+    // All object ids and months are requested at the same time.
+    let resps = try_join_all(requests.iter().map(|(object_id, start, end)| connecteam.timesheet_response(*object_id, user, start, end, timezone))).await?;
+    return Ok(requests.iter().map(|(object_id, _, _)| *object_id).zip(resps).collect());
+}
 
-    #[serde(rename = "defaultTimezone")]
-    default_timezone: String,
+/// Parses the responses of each object id and replaces its shifts of the range in the local mirror.
+fn mirror_responses(store: &mut Store, resps: &[(u64, String)], start: &NaiveDate, end: &NaiveDate, timezone: &Tz) -> Result<usize> {
+    let mut object_ids: Vec<u64> = resps.iter().map(|(object_id, _)| *object_id).collect();
+    object_ids.dedup();
+    let mut mirrored = 0;
+    for object_id in object_ids {
+        let entries = parse_timesheets(resps.iter().filter(|(id, _)| *id == object_id).map(|(_, resp)| resp.clone()).collect())?;
+        store.replace_range(object_id, *start, *end, timezone, &entries)?;
+        mirrored += entries.len();
+    }
+    return Ok(mirrored);
+}
 
-    #[serde(rename = "_spirit")]
-    _spirit: String,
+/// Shifts of the range from the local mirror, says on stderr that they may be out of date.
+fn read_mirror(args: &Cli, start: &NaiveDate, end: &NaiveDate, timezone: &Tz) -> Result<Vec<TimesheetEntry>> {
+    let mirrored = Store::open(&default_store_path())
+        .and_then(|store| store.entries(*start, *end, timezone))
+        .context("Failed to read the local mirror")?;
+    if !args.quiet {
+        match mirrored.synced_at {
+            Some(synced_at) => eprintln!(
+                "{}",
+                format!("Offline: showing the local mirror, last synced {}", synced_at.with_timezone(timezone).format("%Y-%m-%d %H:%M")).yellow()
+            ),
+            None => eprintln!("{}", "Offline: this range was never synced, the local mirror has no shifts for it".yellow()),
+        }
+    }
+    return Ok(mirrored.entries);
 }
 
-pub trait AsVec {
-    type Item;
-    fn as_vec(&self) -> &Vec<Self::Item>;
+/// Shifts of the range from the API, or from the local mirror with `--offline`. Only your own shifts are mirrored.
+async fn load_entries(
+    args: &Cli,
+    user: Option<u64>,
+    client: &ApiClient,
+    config_path: &Path,
+    start: &NaiveDate,
+    end: &NaiveDate,
+    timezone: &Tz,
+) -> Result<Vec<TimesheetEntry>> {
+    if args.offline {
+        if user.is_some() {
+            anyhow::bail!("--offline only has your own shifts, it can't be combined with --user");
+        }
+        return read_mirror(args, start, end, timezone);
+    }
+    let resps = fetch_timesheets(args, user, client, config_path, start, end, timezone).await?;
+    if user.is_none() {
+        // The mirror is only a convenience, failing to update it does not fail the command.
+        let mirrored = Store::open(&default_store_path())
+            .map_err(anyhow::Error::from)
+            .and_then(|mut store| mirror_responses(&mut store, &resps, start, end, timezone));
+        if let Err(err) = mirrored {
+            warn(args, format!("failed to update the local mirror: {}", err));
+        }
+    }
+    return parse_timesheets(resps.into_iter().map(|(_, resp)| resp).collect());
 }
 
-impl AsVec for JsonValue {
-    type Item = JsonValue;
+fn parse_timesheets(resps: Vec<String>) -> Result<Vec<TimesheetEntry>> {
+    let mut entries = vec![];
+    for resp in resps {
+        entries.extend(parse_timesheet(resp).with_context(|| "Failed to parse request")?);
+    }
 
-    fn as_vec(&self) -> &Vec<Self::Item> {
-        if let Array(elem_vec) = self {
-            return elem_vec;
-        }
-        static EMPTY_VEC: Vec<JsonValue> = vec![];
-        return &EMPTY_VEC;
+    // Shifts on the boundary of two monthly requests can be returned by both.
+    let mut seen = HashSet::new();
+    entries.retain(|entry| seen.insert((entry.start, entry.end, entry.project.clone(), entry.subproject.clone())));
+    return Ok(entries);
+}
+
+async fn list_tags(args: &Cli, format: &TagsFormat, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let metas = with_session(args, client, config_path, async |connecteam, object_ids| {
+        return try_join_all(object_ids.iter().map(|object_id| connecteam.punchclock_data(*object_id, timezone))).await;
+    })
+    .await
+    .with_context(|| "Failed to send request \"get punchclock data\"")?;
+
+    // Punchclocks of all object ids are merged into one listing.
+    let mut merged = PunchclockMeta { attachments: vec![], tags: vec![], active_shift: None, active_shift_notes: "".to_string() };
+    for meta in metas {
+        merged.attachments.extend(meta.attachments);
+        merged.tags.extend(meta.tags);
+        merged.active_shift = merged.active_shift.or(meta.active_shift);
     }
+    let rendered = match format {
+        TagsFormat::Table => render_tags(&merged.tags) + "\n",
+        TagsFormat::Json => serde_json::to_string_pretty(&merged)? + "\n",
+    };
+    return write_output(&args.output, &rendered);
 }
 
-fn get_object_id_from_api(session_info: &SessionInfo) -> Result<u64> {
-    let client = reqwest::blocking::Client::new();
+/// Adds a finished shift to the first punchclock and prints it, `find_tags` picks its tag ids and label from the
+/// available tags. `add` and `shift add` only differ in how they read the times and the tag, both end up here.
+async fn add_shift_between(
+    args: &Cli,
+    client: &ApiClient,
+    config_path: &Path,
+    timezone: &Tz,
+    start: DateTime<Tz>,
+    end: DateTime<Tz>,
+    note: &str,
+    find_tags: impl Fn(&[Tag]) -> Result<(u64, Option<u64>, String), ConnecteamError>,
+) -> Result<()> {
+    if end <= start {
+        anyhow::bail!("The end of the shift must be after its start");
+    }
+    let label = with_session(args, client, config_path, async |connecteam, object_ids| {
+        // Shifts are added to the first punchclock unless --object-id picks another one.
+        let object_id = object_ids[0];
+        let meta = connecteam.punchclock_data(object_id, timezone).await?;
+        let (tag_id, sub_tag_id, label) = find_tags(&meta.tags)?;
+        let shift = NewShift {
+            start: start.with_timezone(&Utc),
+            end: end.with_timezone(&Utc),
+            tag_id: tag_id,
+            sub_tag_id: sub_tag_id,
+            note: note.to_string(),
+        };
+        connecteam.add_shift(object_id, &shift, timezone).await?;
+        return Ok(label);
+    })
+    .await
+    .with_context(|| "Failed to add shift")?;
+
+    println!("Added shift {} - {} on {}", start.format("%Y-%m-%d %H:%M"), end.format("%H:%M"), label);
+    return Ok(());
+}
+
+async fn add_shift(args: &Cli, add: &AddArgs, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let AddArgs { start, end, project, subproject, note } = add;
+
+    let now = Utc::now().with_timezone(timezone);
+    let start = parse_date_string(start, now, Dialect::Uk).with_context(|| f!("Failed to parse start: {start}"))?;
+    let end = parse_date_string(end, now, Dialect::Uk).with_context(|| f!("Failed to parse end: {end}"))?;
+    return add_shift_between(args, client, config_path, timezone, start, end, note, |tags| {
+        let (tag_id, sub_tag_id) = find_tag_ids(tags, project, subproject)?;
+        return Ok((tag_id, sub_tag_id, project.clone()));
+    })
+    .await;
+}
+
+/// Local time on `date` in the timezone, refusing times skipped or repeated by a DST change.
+fn local_time(date: NaiveDate, time: NaiveTime, timezone: &Tz) -> Result<DateTime<Tz>> {
+    return timezone
+        .from_local_datetime(&date.and_time(time))
+        .single()
+        .ok_or_else(|| anyhow::anyhow!(format!("{} {} does not exist or is ambiguous in {}", date, time.format("%H:%M"), timezone.name())));
+}
+
+async fn add_shift_on_day(args: &Cli, add: &ShiftAddArgs, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    if add.to == add.from {
+        anyhow::bail!("The shift would be empty, --to must differ from --from");
+    }
+    // "today" and "yesterday" are days in the timezone the times are given in, not on this machine.
+    let date = parse_date_string(&add.date, Utc::now().with_timezone(timezone), Dialect::Uk)
+        .with_context(|| format!("Failed to parse date: {}", add.date))?
+        .date_naive();
+    let start = local_time(date, add.from, timezone)?;
+    // A --to before --from ends the shift on the next day, like a night shift.
+    let end_date = if add.to < add.from { date.succ_opt().with_context(|| format!("No day after {}", date))? } else { date };
+    let end = local_time(end_date, add.to, timezone)?;
+    return add_shift_between(args, client, config_path, timezone, start, end, &add.note, |tags| {
+        let (tag, sub_item) = resolve_tag(tags, &add.tag)?;
+        let label = match sub_item {
+            Some(sub_item) => format!("{} / {}", tag.name, sub_item.name),
+            None => tag.name.clone(),
+        };
+        return Ok((tag.id, sub_item.map(|sub_item| sub_item.id), label));
+    })
+    .await;
+}
+
+/// Shifts are searched this many days back when `shift edit` is not told the day.
+const EDIT_SEARCH_DAYS: i64 = 62;
+
+/// Looks up one of your shifts by id, on `date` or within the last `EDIT_SEARCH_DAYS` days, with the object id of
+/// the punchclock it belongs to.
+async fn find_shift(args: &Cli, client: &ApiClient, config_path: &Path, timezone: &Tz, id: &str, date: &Option<String>) -> Result<(u64, TimesheetEntry)> {
+    let now = Utc::now().with_timezone(timezone);
+    let today = now.date_naive();
+    let (start, end) = match date {
+        Some(date) => {
+            let date = parse_date_string(date, now, Dialect::Uk).with_context(|| format!("Failed to parse date: {}", date))?.date_naive();
+            (date, date)
+        }
+        None => (today - chrono::Duration::days(EDIT_SEARCH_DAYS), today),
+    };
+    let found = with_session(args, client, config_path, async |connecteam, object_ids| {
+        // Changes have to go to the punchclock the shift is in, so every one is searched on its own.
+        for object_id in object_ids.iter() {
+            let entries = connecteam.timesheet(*object_id, None, &start, &end, timezone).await?;
+            if let Some(entry) = entries.into_iter().find(|entry| entry.id == id) {
+                return Ok(Some((*object_id, entry)));
+            }
+        }
+        return Ok(None);
+    })
+    .await
+    .with_context(|| "Failed to fetch the timesheet")?;
+    let Some(found) = found else {
+        anyhow::bail!("No shift with id {} from {} to {}, pass --date with the day it started on", id, start, end);
+    };
+    return Ok(found);
+}
+
+async fn edit_shift(args: &Cli, edit_args: &ShiftEditArgs, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let (object_id, entry) = &find_shift(args, client, config_path, timezone, &edit_args.id, &edit_args.date).await?;
+    let Some(current) = edit::ShiftFields::from_entry(entry, timezone) else {
+        anyhow::bail!("The shift is still running, punch out before editing it");
+    };
+
+    let edited = if edit_args.interactive {
+        let edited = edit::edit_in_editor(&current)?;
+        let changes = current.changes(&edited);
+        for (name, old, new) in &changes {
+            println!("{}: {} -> {}", name, old.red(), new.green());
+        }
+        let confirmed = !changes.is_empty()
+            && dialoguer::Confirm::new()
+                .with_prompt("Submit these changes?")
+                .default(false)
+                .interact()
+                .context("Failed to ask for confirmation")?;
+        if !confirmed {
+            println!("The shift is left as it is");
+            return Ok(());
+        }
+        edited
+    } else {
+        let base = entry.start.with_timezone(timezone);
+        let parse_time = |text: &str, name: &str| -> Result<NaiveDateTime> {
+            let time = parse_date_string(text, base, Dialect::Uk).with_context(|| format!("Failed to parse {}: {}", name, text))?;
+            return Ok(time.naive_local());
+        };
+        edit::ShiftFields {
+            start: edit_args.start.as_deref().map(|start| parse_time(start, "start")).transpose()?.unwrap_or(current.start),
+            end: edit_args.end.as_deref().map(|end| parse_time(end, "end")).transpose()?.unwrap_or(current.end),
+            tag: edit_args.tag.clone().unwrap_or_else(|| current.tag.clone()),
+            note: edit_args.note.clone().unwrap_or_else(|| current.note.clone()),
+        }
+    };
+    if edited == current {
+        println!("Nothing to change");
+        return Ok(());
+    }
+
+    let start = local_time(edited.start.date(), edited.start.time(), timezone)?;
+    let end = local_time(edited.end.date(), edited.end.time(), timezone)?;
+    if end <= start {
+        anyhow::bail!("The end of the shift must be after its start");
+    }
+    with_session(args, client, config_path, async |connecteam, _| {
+        // The tags are those of the punchclock the shift is in.
+        let meta = connecteam.punchclock_data(*object_id, timezone).await?;
+        let (tag, sub_item) = resolve_tag(&meta.tags, &edited.tag)?;
+        let shift = NewShift {
+            start: start.with_timezone(&Utc),
+            end: end.with_timezone(&Utc),
+            tag_id: tag.id,
+            sub_tag_id: sub_item.map(|sub_item| sub_item.id),
+            note: edited.note.clone(),
+        };
+        return connecteam.edit_shift(*object_id, &edit_args.id, &shift, timezone).await;
+    })
+    .await
+    .with_context(|| "Failed to edit the shift")?;
 
-    let resp_raw = client
-        .get("https://app.connecteam.com/api/UserDashboard/ContentStructure/")
-        .header(
-            "cookie",
-            f!("session={session_info.session}; _spirit={session_info.spirit}; "),
-        )
-        .send();
+    println!("Updated shift {} - {} on {}", start.format("%Y-%m-%d %H:%M"), end.format("%H:%M"), edited.tag);
+    return Ok(());
+}
 
-    let resp_raw = resp_raw.context("Sending request for content structure failed")?;
-    if resp_raw.status() != 200 {
-        return Err(anyhow::anyhow!(format!("Response for content structure nok: {:?}", resp_raw.status())));
+async fn delete_shift(args: &Cli, delete_args: &ShiftDeleteArgs, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let (object_id, entry) = find_shift(args, client, config_path, timezone, &delete_args.id, &delete_args.date).await?;
+    let description = describe_shift(&entry, timezone);
+    if !delete_args.yes {
+        let confirmed = dialoguer::Confirm::new()
+            .with_prompt(format!("Delete {}?", description))
+            .default(false)
+            .interact()
+            .context("Failed to ask for confirmation, pass --yes to delete without asking")?;
+        if !confirmed {
+            println!("The shift is left as it is");
+            return Ok(());
+        }
     }
-    let resp = resp_raw.text()?;
 
-    let parsed = json::parse(&resp);
-    let containers = &parsed.context(format!("response {:?} for content structure doesn't contain fields data:containers", resp))?["data"]["containers"];
+    with_session(args, client, config_path, async |connecteam, _| {
+        return connecteam.delete_shift(object_id, &delete_args.id).await;
+    })
+    .await
+    .with_context(|| "Failed to delete the shift")?;
+
+    println!("Deleted {}", description);
+    return Ok(());
+}
 
-    let object_ids = containers
-        .as_vec()
+/// Asks which project and subproject to punch in on, for `punch in` without --project.
+fn pick_tag(tags: &[Tag]) -> Result<(&Tag, Option<&Tag>), ConnecteamError> {
+    if !std::io::stdin().is_terminal() {
+        return Err(ConnecteamError::UnknownTag("no --project given and no terminal to pick one in".to_string()));
+    }
+    let mut choices: Vec<(&Tag, Option<&Tag>)> = vec![];
+    for tag in tags {
+        if tag.sub_items.is_empty() {
+            choices.push((tag, None));
+        }
+        choices.extend(tag.sub_items.iter().map(|sub_item| (tag, Some(sub_item))));
+    }
+    if choices.is_empty() {
+        return Err(ConnecteamError::UnknownTag("the punchclock has no projects to pick from".to_string()));
+    }
+    let labels: Vec<String> = choices
         .iter()
-        .filter(|x| x["name"] == "Operations")
-        .flat_map(|x| x["assets"].as_vec())
-        .filter(|x| x["dashboardType"] == "punchclock")
-        .flat_map(|x| x["courses"].as_vec())
-        .flat_map(|x| x["sections"].as_vec())
-        .flat_map(|x| x["objects"].as_vec())
-        .filter_map(|x| match &x["id"] {
-            Number(val) => Some(val),
-            _ => None,
+        .map(|(tag, sub_item)| match sub_item {
+            Some(sub_item) => format!("{} / {}", tag.name, sub_item.name),
+            None => tag.name.clone(),
         })
-        .collect::<Vec<_>>();
+        .collect();
+    let picked = dialoguer::FuzzySelect::new()
+        .with_prompt("Project")
+        .items(&labels)
+        .default(0)
+        .interact_opt()
+        .map_err(|err| std::io::Error::other(err.to_string()))?;
+    return match picked {
+        Some(index) => Ok(choices[index]),
+        None => Err(ConnecteamError::UnknownTag("no project picked".to_string())),
+    };
+}
 
-    assert!(object_ids.len() != 0);
-    if object_ids.len() > 1 {
-        println!("WARN: Found more then one matching object id!");
+/// Punches the clock now and returns the clock state after the punch.
+async fn punch_clock(args: &Cli, action: &PunchAction, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<ClockState> {
+    let now = Utc::now();
+    let shift = with_session(args, client, config_path, async |connecteam, object_ids| {
+        // Like added shifts, punches go to the first punchclock unless --object-id picks another one.
+        let object_id = object_ids[0];
+        match action {
+            PunchAction::In(punch_in) => {
+                let meta = connecteam.punchclock_data(object_id, timezone).await?;
+                let (tag_id, sub_tag_id, project, subproject) = match (&punch_in.project, &punch_in.tag) {
+                    (Some(project), _) => {
+                        let (tag_id, sub_tag_id) = find_tag_ids(&meta.tags, project, &punch_in.subproject)?;
+                        (tag_id, sub_tag_id, project.clone(), punch_in.subproject.clone().unwrap_or_default())
+                    }
+                    (None, tag) => {
+                        let (tag, sub_item) = match tag {
+                            Some(query) => resolve_tag(&meta.tags, query)?,
+                            None => pick_tag(&meta.tags)?,
+                        };
+                        (tag.id, sub_item.map(|sub_item| sub_item.id), tag.name.clone(), sub_item.map(|sub_item| sub_item.name.clone()).unwrap_or_default())
+                    }
+                };
+                connecteam.punch_in(object_id, tag_id, sub_tag_id, now, timezone).await?;
+                return Ok(Some(TimesheetEntry {
+                    id: "".to_string(),
+                    start: now,
+                    end: None,
+                    desc: "".to_string(),
+                    notes: "".to_string(),
+                    project: project,
+                    subproject: subproject,
+                    breaks: vec![],
+                    overlap: chrono::Duration::zero(),
+                }));
+            }
+            PunchAction::Out(punch_out) => {
+                connecteam.punch_out(object_id, &punch_out.note, now, timezone).await?;
+                return Ok(None);
+            }
+        }
+    })
+    .await
+    .with_context(|| "Failed to punch the clock")?;
+
+    let state = ClockState { checked_at: now, shift: shift };
+    save_clock_state(&default_clock_state_path(), &state);
+    return Ok(state);
+}
+
+async fn punch(args: &Cli, action: &PunchAction, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let state = punch_clock(args, action, client, config_path, timezone).await?;
+    let time = state.checked_at.with_timezone(timezone).format("%H:%M");
+    match &state.shift {
+        Some(shift) => println!("Punched in on {} at {}", shift_project(shift), time),
+        None => println!("Punched out at {}", time),
     }
-    return Ok(object_ids[0].as_fixed_point_u64(0).unwrap());
+    return Ok(());
 }
 
-fn send_request_get_timesheet(session_info: &SessionInfo, start: &DateTime<Local>, end: &DateTime<Local>) -> Result<String> {
-    let client = reqwest::blocking::Client::new();
+async fn set_note(args: &Cli, note_args: &NoteArgs, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let noted = with_session(args, client, config_path, async |connecteam, object_ids| {
+        // The note goes to whichever punchclock you are punched in on.
+        for object_id in object_ids.iter() {
+            let meta = connecteam.punchclock_data(*object_id, timezone).await?;
+            let Some(shift) = meta.active_shift else { continue };
+            // Only the notes are sent back, the shift's desc also holds the attachment texts.
+            let note = match meta.active_shift_notes.as_str() {
+                "" => note_args.text.clone(),
+                _ if note_args.replace => note_args.text.clone(),
+                notes => format!("{}; {}", notes, note_args.text),
+            };
+            connecteam.set_shift_note(*object_id, &note, timezone).await?;
+            return Ok(Some((shift, note)));
+        }
+        return Ok(None);
+    })
+    .await
+    .with_context(|| "Failed to set the note")?;
 
-    let request_payload = TimesheetParams {
-        start_date: start.format("%Y-%m-%d").to_string(),
-        end_date: end.format("%Y-%m-%d").to_string(),
-        object_id: get_object_id_from_api(&session_info).context("getting object id failed")?,
-        default_timezone: "Europe/Warsaw".to_string(),
-        _spirit: session_info.spirit.clone(),
+    let Some((shift, note)) = noted else {
+        anyhow::bail!("You are not punched in, notes of finished shifts can be changed with shift edit --note");
     };
+    println!("Note of the shift on {}: {}", shift_project(&shift), note);
+    return Ok(());
+}
 
-    let resp_raw = client
-        .post("https://app.connecteam.com/api/UserDashboard/PunchClock/Timesheet/")
-        .header(
-            "cookie",
-            f!("session={session_info.session}; _spirit={session_info.spirit}; "),
-        )
-        .body(json!(request_payload).to_string())
-        .send();
-    
-    let resp_raw = resp_raw.with_context(|| format!("Response for get timesheet nok "))?;
-    let result = resp_raw.text().with_context(|| format!("Response for get timesheet nok "))?;
-    return Ok(result);
-}
-
-fn parse_timesheet(resp: String) -> Result<Vec<TimesheetEntry>> {
-    let parsed = json::parse(&resp);
-    let time_sheet_entries = &parsed?["data"]["userTimeSheets"]["timeSheetEntries"];
-
-    let timesheet_entries = time_sheet_entries
-        .as_vec()
-        .iter()
-        .flat_map(|x| x["timeSheetDayEntries"].as_vec())
-        .flat_map(|x| x["shifts"].as_vec())
-        .map(|shift| {
-            let parse_timestamp = |timestamp: &JsonValue| {
-                let seconds_since_epoch = timestamp["timestampWithTimezone"]["timestamp"]
-                    .as_i64()
-                    .unwrap();
-                return Utc.timestamp_opt(seconds_since_epoch, 0).unwrap();
-            };
-            
-            let mut desc = "".to_string();
-            let free_text = shift["shiftAttachments"].as_vec()[0]["freeText"].to_string();
-            let notes = shift["employeeNotes"].to_string();
-
-            if !(free_text == "" || free_text == "null") && notes != "" {
-                desc = f!("{free_text} / {notes}");
-            } else if !(free_text == "" || free_text == "null") {
-                desc = free_text;
-            } else if notes != "" {
-                desc = notes;
+/// Runs the calendar TUI, fetching the shown month and punching whenever it asks for it.
+async fn browse(args: &Cli, tui_args: &TuiArgs, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let today = Utc::now().with_timezone(timezone).date_naive();
+    let selected = tui_args.month.unwrap_or(today);
+    let (start, end) = dates::this_month(selected);
+    let mut calendar = tui::Calendar::new(selected, timezone, tui_args.week_start);
+    calendar.set_entries(start, load_entries(args, None, client, config_path, &start, &end, timezone).await?);
+
+    let mut terminal = ratatui::init();
+    let result = loop {
+        let action = match calendar.run_until_action(&mut terminal) {
+            Ok(action) => action,
+            Err(err) => break Err(anyhow::Error::from(err)),
+        };
+        let punch_action = match action {
+            tui::CalendarAction::Quit => break Ok(()),
+            tui::CalendarAction::LoadMonth(month) => {
+                let (start, end) = dates::this_month(month);
+                match load_entries(args, None, client, config_path, &start, &end, timezone).await {
+                    Ok(entries) => calendar.set_entries(month, entries),
+                    Err(err) => calendar.set_message(format!("Failed to load {}: {}", month.format("%B %Y"), err)),
+                }
+                continue;
             }
+            tui::CalendarAction::PunchIn(project) => PunchAction::In(PunchInArgs { project: None, subproject: None, tag: Some(project) }),
+            tui::CalendarAction::PunchOut => PunchAction::Out(PunchOutArgs { note: "".to_string() }),
+        };
+        if args.offline {
+            calendar.set_message("Punching needs the API, it is not possible with --offline".to_string());
+            continue;
+        }
+        // The punch changes today's shifts, so the month is fetched again after it.
+        let message = match punch_clock(args, &punch_action, client, config_path, timezone).await {
+            Ok(state) => match &state.shift {
+                Some(shift) => format!("Punched in on {} at {}", shift_project(shift), state.checked_at.with_timezone(timezone).format("%H:%M")),
+                None => format!("Punched out at {}", state.checked_at.with_timezone(timezone).format("%H:%M")),
+            },
+            Err(err) => format!("Failed to punch the clock: {:#}", err),
+        };
+        let (start, end) = dates::this_month(calendar.month());
+        if let Ok(entries) = load_entries(args, None, client, config_path, &start, &end, timezone).await {
+            calendar.set_entries(start, entries);
+        }
+        calendar.set_message(message);
+    };
+    ratatui::restore();
+    return result;
+}
+
+async fn write_report(args: &Cli, report: &ReportArgs, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let month = report.month.unwrap_or_else(|| Utc::now().with_timezone(timezone).date_naive());
+    let (start, end) = dates::this_month(month);
+    let mut entries = load_entries(args, None, client, config_path, &start, &end, timezone).await?;
+    apply_filters(&mut entries, &report.filter);
+
+    let rate = report.rate.map(|amount| Rate { amount: amount, currency: report.currency.clone() });
+    let title = format!("Timesheet {}", start.format("%B %Y"));
+    let (rendered, extension) = match report.kind {
+        ReportKind::Html => (render_html_report(&mut entries, timezone, &title, &rate).into_bytes(), "html"),
+        ReportKind::Pdf => {
+            entries.sort_by_key(|k| k.start);
+            (pdf::render_pdf_report(&entries, timezone, &title, &report.employee), "pdf")
+        }
+    };
+
+    // Reports are meant to be sent around, so they always go to a file.
+    let path = args.output.clone().unwrap_or_else(|| PathBuf::from(format!("timesheet-{}.{}", start.format("%Y-%m"), extension)));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, rendered).with_context(|| format!("Failed to write {:?}", path))?;
+    if !args.quiet {
+        eprintln!("Report written to {}", path.display());
+    }
+    return Ok(());
+}
+
+async fn export(args: &Cli, export: &ExportArgs, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let from = export.from.unwrap_or_else(|| Utc::now().with_timezone(timezone).date_naive());
+    let (start, _) = dates::this_month(from);
+    let (_, end) = dates::this_month(export.to.unwrap_or(from));
+    if end < start {
+        anyhow::bail!("--to must not be before --from");
+    }
+    let mut entries = load_entries(args, None, client, config_path, &start, &end, timezone).await?;
+    apply_filters(&mut entries, &export.filter);
+    entries.sort_by_key(|k| k.start);
+
+    let (exported, extension) = match export.kind {
+        ExportKind::Xlsx => (xlsx::export_xlsx(&entries, timezone).context("Failed to create the workbook")?, "xlsx"),
+    };
+    let range = if start.month() == end.month() && start.year() == end.year() {
+        start.format("%Y-%m").to_string()
+    } else {
+        format!("{}_{}", start.format("%Y-%m"), end.format("%Y-%m"))
+    };
+    let path = args.output.clone().unwrap_or_else(|| PathBuf::from(format!("timesheet-{}.{}", range, extension)));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, exported).with_context(|| format!("Failed to write {:?}", path))?;
+    if !args.quiet {
+        eprintln!("Export written to {}", path.display());
+    }
+    return Ok(());
+}
+
+/// Exit code of `status` when no shift is running, so scripts can tell it apart from errors.
+const CLOCKED_OUT_EXIT_CODE: u8 = 6;
+
+/// Exit code of `check` when it found problems, so it can guard scripts and hooks.
+const CHECK_FAILED_EXIT_CODE: u8 = 7;
 
-            TimesheetEntry {
-                start: parse_timestamp(&shift["punchIn"]),
-                end: parse_timestamp(&shift["punchOut"]),
-                desc: desc,
-                project: shift["punchTag"]["name"].to_string(),
-                subproject: shift["punchTag"]["subItems"][0]["name"].to_string(),
+/// Prints what the check found and returns whether the shifts passed it.
+async fn run_check(args: &Cli, action: &CheckAction, defaults: &Defaults, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<bool> {
+    match action {
+        CheckAction::Gaps(check_args) => {
+            let (start, end) = resolve_range(&check_args.range, timezone)?;
+            // Today is not over yet and the days to come can't have shifts.
+            let end = end.min(Utc::now().with_timezone(timezone).date_naive().pred_opt().unwrap());
+            if !args.quiet {
+                eprintln!("Looking for entries in between: {} - {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
             }
-        })
-        .collect();
+            let entries = load_entries(args, None, client, config_path, &start, &end, timezone).await?;
+            let gaps = checks::find_gaps(&entries, timezone, start, end, &defaults.workdays(), &defaults.holidays);
+            write_output(&args.output, &render_gaps(&gaps))?;
+            return Ok(gaps.is_empty());
+        }
+        CheckAction::Overlaps(check_args) => {
+            let (start, end) = resolve_range(&check_args.range, timezone)?;
+            if !args.quiet {
+                eprintln!("Looking for entries in between: {} - {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
+            }
+            let mut entries = load_entries(args, None, client, config_path, &start, &end, timezone).await?;
+            entries.sort_by_key(|k| k.start);
+            let overlaps = checks::find_overlaps(&entries);
+            write_output(&args.output, &render_overlaps(&entries, &overlaps, timezone))?;
+            return Ok(overlaps.is_empty());
+        }
+        CheckAction::Anomalies(check_args) => {
+            let (start, end) = resolve_range(&check_args.range, timezone)?;
+            if !args.quiet {
+                eprintln!("Looking for entries in between: {} - {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
+            }
+            let mut entries = load_entries(args, None, client, config_path, &start, &end, timezone).await?;
+            entries.sort_by_key(|k| k.start);
+            let anomalies: Vec<(&TimesheetEntry, Vec<checks::Anomaly>)> = entries
+                .iter()
+                .map(|entry| (entry, checks::find_anomalies(entry, timezone, &defaults.anomalies)))
+                .filter(|(_, reasons)| !reasons.is_empty())
+                .collect();
+            write_output(&args.output, &render_anomalies(&anomalies, timezone))?;
+            return Ok(anomalies.is_empty());
+        }
+    }
+}
+
+/// How old the clock state may be for the status bar formats, bars poll far more often than it changes.
+const STATUS_BAR_MAX_AGE_SECS: u64 = 30;
+
+/// Prints the running shift and returns whether there is one.
+async fn show_status(args: &Cli, status_args: &StatusArgs, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<bool> {
+    let max_age = status_args.max_age.unwrap_or(match status_args.format {
+        StatusFormat::Text => 0,
+        StatusFormat::Line | StatusFormat::Waybar | StatusFormat::Prompt => STATUS_BAR_MAX_AGE_SECS,
+    });
+    let metas = with_session(args, client, config_path, async |connecteam, object_ids| {
+        let max_age = std::time::Duration::from_secs(max_age);
+        return try_join_all(object_ids.iter().map(|object_id| connecteam.cached_punchclock_data(*object_id, timezone, max_age))).await;
+    })
+    .await
+    .with_context(|| "Failed to send request \"get punchclock data\"")?;
 
-    return Ok(timesheet_entries);
+    let shift = metas.into_iter().find_map(|meta| meta.active_shift);
+    save_clock_state(&default_clock_state_path(), &ClockState { checked_at: Utc::now(), shift: shift.clone() });
+    match status_args.format {
+        StatusFormat::Text => match &shift {
+            Some(shift) => println!(
+                "Clocked in since {} on {} ({})",
+                shift.start.with_timezone(timezone).format("%Y-%m-%d %H:%M"),
+                shift_project(shift),
+                format_duration(elapsed(shift))
+            ),
+            None => println!("Clocked out"),
+        },
+        StatusFormat::Line => print!("{}", render_status_line(shift.as_ref())),
+        StatusFormat::Waybar => print!("{}", render_waybar_status(shift.as_ref(), timezone)?),
+        StatusFormat::Prompt => print!("{}", render_prompt_status(shift.as_ref())),
+    }
+    return Ok(shift.is_some());
 }
 
+/// Prints a warning about the data to stderr, so it never ends up in the report, unless --quiet is given.
+fn warn(args: &Cli, message: String) {
+    if !args.quiet {
+        eprintln!("Warning: {}", message);
+    }
+}
 
-fn load_session_info_or_ask_user() -> Result<SessionInfo> {
-    let mut session_info_file = home::home_dir().unwrap();
-    session_info_file.push(".config/connectteam.json");
+fn write_output(output: &Option<PathBuf>, rendered: &str) -> Result<()> {
+    match output {
+        Some(path) => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(path, rendered).with_context(|| format!("Failed to write {:?}", path))?;
+        }
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
 
-    if session_info_file.exists() {
-        let info_json = std::fs::read_to_string(session_info_file)?;
-        let session_info: SessionInfo = serde_json::from_str(&info_json)?;
-        return Ok(session_info);
-    } else {
-        println!("Session information are not stored in {}. Please go to https://app.connecteam.com/, login in, open developer console \
-        (ctrl+shift+c in most browsers), go to network, open time clock page in the browsers, navigate to Timesheet request, copy cookie values from request header, copy response to clipboard and past here:", session_info_file.to_str().unwrap());
+/// Maps the error that ended the run to the exit codes listed in the help.
+fn exit_code(err: &anyhow::Error) -> u8 {
+    if let Some(err) = err.downcast_ref::<ConnecteamError>() {
+        return err.exit_code();
+    }
+    if err.downcast_ref::<reqwest::Error>().is_some() {
+        return 3;
+    }
+    if err.downcast_ref::<std::io::Error>().is_some() {
+        return 5;
+    }
+    return 1;
+}
+
+#[tokio::main]
+async fn main() -> std::process::ExitCode {
+    match run().await {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            std::process::ExitCode::from(exit_code(&err))
+        }
+    }
+}
 
-        let mut user_input = String::new();
-        let stdin = std::io::stdin();
-        stdin.read_line(&mut user_input)?;
+async fn run() -> Result<std::process::ExitCode> {
+    let defaults = config::load_defaults(&config::default_defaults_path()).context("Failed to read the defaults file")?;
+    let args = cli::parse_with_defaults(&defaults);
+    let log_level = match args.verbose {
+        0 => "warn",
+        1 => "info",
+        _ => "debug",
+    };
+    let log_filter = if args.quiet { "error".to_string() } else { f!("warn,connectteam_cli={log_level}") };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_filter)).init();
 
-        let mut user_input = user_input.trim().to_string();
-        if user_input.starts_with("'") {
-            user_input.remove(0);
+    colored::control::set_override(match args.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            // Prompts capture the fragment through a pipe but show it on the terminal.
+            let prompt = matches!(&args.command, Some(Command::Status(StatusArgs { format: StatusFormat::Prompt, .. })));
+            args.output.is_none() && (std::io::stdout().is_terminal() || prompt) && std::env::var_os("NO_COLOR").is_none()
         }
-        if user_input.ends_with("'") {
-            user_input.remove(user_input.len() - 1);
+    });
+
+    // The prompt runs before every command line, so it only reads the clock state saved by status and punch.
+    if matches!(&args.command, Some(Command::Status(StatusArgs { format: StatusFormat::Prompt, .. }))) {
+        let state = load_clock_state(&default_clock_state_path());
+        print!("{}", render_prompt_status(state.as_ref().and_then(|state| state.shift.as_ref())));
+        return Ok(std::process::ExitCode::SUCCESS);
+    }
+
+    let config_path = args.config.clone().unwrap_or_else(default_session_info_path);
+    if args.offline && !matches!(args.command, None | Some(Command::Timesheet(_)) | Some(Command::Summary(_)) | Some(Command::Stats(_)) | Some(Command::Overtime(_)) | Some(Command::Earnings(_)) | Some(Command::Invoice(_)) | Some(Command::Tui(_)) | Some(Command::Check { .. }) | Some(Command::Report(_)) | Some(Command::Export(_))) {
+        anyhow::bail!("--offline only works with the timesheet, summary, stats, overtime, earnings, invoice, tui, check, report and export commands");
+    }
+    // Every redraw of --watch asks the API again, cached responses would show the same shifts for the whole TTL.
+    let watching = match &args.command {
+        None => args.timesheet.watch.is_some(),
+        Some(Command::Timesheet(timesheet)) => timesheet.watch.is_some(),
+        Some(Command::Status(status_args)) => status_args.watch.is_some(),
+        _ => false,
+    };
+    let cache_mode = match (args.no_cache, args.refresh || watching) {
+        (true, _) => CacheMode::Off,
+        (false, true) => CacheMode::Refresh,
+        (false, false) => CacheMode::Use,
+    };
+    let cache = ResponseCache::new(default_cache_dir(), std::time::Duration::from_secs(args.cache_ttl), cache_mode);
+    let client = api::build_client(&args.base_url, args.attempts, &args.proxy, &args.ca_cert, cache).context("Failed to create http client")?;
+
+    match &args.command {
+        Some(Command::Timesheet(timesheet)) => watch_timesheet(&args, timesheet, &defaults.anomalies, &client, &config_path, &resolve_timezone(&args.timezone)?).await?,
+        Some(Command::Tags { format }) => list_tags(&args, format, &client, &config_path, &resolve_timezone(&args.timezone)?).await?,
+        Some(Command::Login(login_args)) => log_in(&args, login_args, &client, &config_path).await?,
+        Some(Command::Add(add)) => add_shift(&args, add, &client, &config_path, &resolve_timezone(&args.timezone)?).await?,
+        Some(Command::Status(status_args)) => loop {
+            if status_args.watch.is_some() && status_args.format == StatusFormat::Text {
+                print!("{}", CLEAR_SCREEN);
+            }
+            let clocked_in = show_status(&args, status_args, &client, &config_path, &resolve_timezone(&args.timezone)?).await?;
+            match status_args.watch {
+                // Status bars reading the output line by line pick up every new line.
+                Some(seconds) => {
+                    std::io::stdout().flush()?;
+                    tokio::time::sleep(std::time::Duration::from_secs(seconds)).await;
+                }
+                // Status bars run the command every few seconds and treat failing commands as broken.
+                None if !clocked_in && status_args.format == StatusFormat::Text => {
+                    return Ok(std::process::ExitCode::from(CLOCKED_OUT_EXIT_CODE));
+                }
+                None => break,
+            }
+        },
+        Some(Command::Report(report)) => write_report(&args, report, &client, &config_path, &resolve_timezone(&args.timezone)?).await?,
+        Some(Command::Export(export_args)) => export(&args, export_args, &client, &config_path, &resolve_timezone(&args.timezone)?).await?,
+        Some(Command::Punch { action }) => punch(&args, action, &client, &config_path, &resolve_timezone(&args.timezone)?).await?,
+        Some(Command::Note(note_args)) => set_note(&args, note_args, &client, &config_path, &resolve_timezone(&args.timezone)?).await?,
+        Some(Command::Summary(summary)) => show_summary(&args, summary, &client, &config_path, &resolve_timezone(&args.timezone)?).await?,
+        Some(Command::Stats(stats_args)) => show_stats(&args, stats_args, &client, &config_path, &resolve_timezone(&args.timezone)?).await?,
+        Some(Command::Overtime(overtime_args)) => {
+            show_overtime(&args, overtime_args, &defaults.contract, &client, &config_path, &resolve_timezone(&args.timezone)?).await?
         }
+        Some(Command::Earnings(earnings_args)) => {
+            show_earnings(&args, earnings_args, &defaults.rates, &client, &config_path, &resolve_timezone(&args.timezone)?).await?
+        }
+        Some(Command::Invoice(invoice_args)) => {
+            write_invoice(&args, invoice_args, &defaults, &client, &config_path, &resolve_timezone(&args.timezone)?).await?
+        }
+        Some(Command::Sync(sync_args)) => sync(&args, sync_args, &client, &config_path, &resolve_timezone(&args.timezone)?).await?,
+        Some(Command::Shift { action: ShiftAction::Add(shift_add) }) => {
+            add_shift_on_day(&args, shift_add, &client, &config_path, &resolve_timezone(&args.timezone)?).await?
+        }
+        Some(Command::Shift { action: ShiftAction::Edit(shift_edit) }) => {
+            edit_shift(&args, shift_edit, &client, &config_path, &resolve_timezone(&args.timezone)?).await?
+        }
+        Some(Command::Shift { action: ShiftAction::Delete(shift_delete) }) => {
+            delete_shift(&args, shift_delete, &client, &config_path, &resolve_timezone(&args.timezone)?).await?
+        }
+        Some(Command::Tui(tui_args)) => browse(&args, tui_args, &client, &config_path, &resolve_timezone(&args.timezone)?).await?,
+        Some(Command::Check { action }) => {
+            if !run_check(&args, action, &defaults, &client, &config_path, &resolve_timezone(&args.timezone)?).await? {
+                return Ok(std::process::ExitCode::from(CHECK_FAILED_EXIT_CODE));
+            }
+        }
+        None => watch_timesheet(&args, &args.timesheet, &defaults.anomalies, &client, &config_path, &resolve_timezone(&args.timezone)?).await?,
+    }
+    return Ok(std::process::ExitCode::SUCCESS);
+}
 
-        let extract_field_from_cookie = |field| {
-            user_input
-                .split(";")
-                .map(|x| x.split("=").map(|x| x.trim()).collect::<Vec<_>>())
-                .filter(|x| x.len() == 2)
-                .filter(|x| x[0] == field)
-                .flatten()
-                .collect::<Vec<_>>()[1]
-        };
+/// Fetches the days since the last sync of each punchclock into the local mirror.
+async fn sync(args: &Cli, sync_args: &SyncArgs, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let now = Utc::now().with_timezone(timezone);
+    let today = now.date_naive();
+    let since = parse_date_string(&sync_args.since, now, Dialect::Uk)
+        .with_context(|| f!("Failed to parse start date: {sync_args.since}"))?
+        .date_naive();
+    let mut store = Store::open(&default_store_path()).context("Failed to open the local mirror")?;
 
-        let session_info = SessionInfo {
-            session: extract_field_from_cookie("session").to_string(),
-            spirit: extract_field_from_cookie("_spirit").to_string(),
-        };
+    let (ranges, resps) = with_session(args, client, config_path, async |connecteam, object_ids| {
+        let mut ranges = vec![];
+        for object_id in object_ids {
+            // Recent shifts are still edited and punched out, so the last days are fetched again.
+            let start = match store.synced_until(object_id)? {
+                Some(synced_until) if !sync_args.full => (synced_until - chrono::Duration::days(sync_args.lookback)).max(since),
+                _ => since,
+            };
+            ranges.push((object_id, start));
+        }
+        let resps = fetch_ranges(connecteam, None, &ranges, &today, timezone).await?;
+        return Ok((ranges, resps));
+    })
+    .await
+    .with_context(|| "Failed to send request \"get timesheet\"")?;
+
+    for (object_id, start) in ranges {
+        let object_resps: Vec<(u64, String)> = resps.iter().filter(|(id, _)| *id == object_id).cloned().collect();
+        let mirrored = mirror_responses(&mut store, &object_resps, &start, &today, timezone).context("Failed to update the local mirror")?;
+        store.set_synced_until(object_id, today).context("Failed to update the local mirror")?;
+        if !args.quiet {
+            eprintln!("Synced {} shifts of punchclock {} from {} to {}", mirrored, object_id, start.format("%Y-%m-%d"), today.format("%Y-%m-%d"));
+        }
+    }
+    return Ok(());
+}
 
-        std::fs::write(
-            session_info_file,
-            serde_json::to_string_pretty(&session_info).unwrap(),
-        )?;
-        return Ok(session_info);
+async fn log_in(args: &Cli, login_args: &LoginArgs, client: &ApiClient, config_path: &Path) -> Result<()> {
+    if login_args.encrypt {
+        config::ask_new_passphrase().context("Failed to set the passphrase")?;
+    }
+    let identity = match (&login_args.email, &login_args.phone) {
+        (Some(email), _) => Some(LoginIdentity::Email(email.clone())),
+        (None, Some(phone)) => Some(LoginIdentity::Phone(phone.clone())),
+        (None, None) => None,
+    };
+    match (identity, &login_args.from_browser) {
+        (Some(identity), _) => login_with_code(client, config_path, !args.no_save, &args.container, &identity).await,
+        (None, Some(browser)) => login_from_browser(client, config_path, !args.no_save, &args.container, browser).await,
+        (None, None) => login(client, config_path, !args.no_save, &args.container).await,
     }
+    .with_context(|| "Failed to log in")?;
+    if args.no_save {
+        println!("Session is valid, not storing it because of --no-save");
+    } else {
+        println!("Session stored for {}", config_path.display());
+    }
+    return Ok(());
 }
 
-fn draw_timesheet(entries: &mut Vec<TimesheetEntry>) {
+/// First and last day selected by the range options, in the display timezone.
+fn resolve_range(range: &RangeArgs, timezone: &Tz) -> Result<(NaiveDate, NaiveDate)> {
+    // All range options count days in the display timezone, "today" is the same day for --start and --this-week.
+    let now = Utc::now().with_timezone(timezone);
+    let today = now.date_naive();
+    let days = if let Some(month) = range.month {
+        dates::this_month(month)
+    } else if range.this_week {
+        dates::this_week(today, range.week_start)
+    } else if range.last_week {
+        dates::last_week(today, range.week_start)
+    } else if range.this_month {
+        dates::this_month(today)
+    } else if range.last_month {
+        dates::last_month(today)
+    } else {
+        let start = parse_date_string(&range.start, now, Dialect::Uk).with_context(|| f!("Failed to parse start date: {range.start}"))?;
+        let end = parse_date_string(&range.end, now, Dialect::Uk).with_context(|| f!("Failed to parse end date: {range.end}"))?;
+        (start.date_naive(), end.date_naive())
+    };
+    return Ok(days);
+}
+
+async fn show_summary(args: &Cli, summary: &SummaryArgs, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let (start, end) = resolve_range(&summary.range, timezone)?;
+    if !args.quiet {
+        eprintln!("Looking for entries in between: {} - {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
+    }
+    let mut entries = load_entries(args, None, client, config_path, &start, &end, timezone).await?;
+    apply_filters(&mut entries, &summary.filter);
+    if entries.is_empty() {
+        println!("No shifts found for {} - {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
+        return Ok(());
+    }
+    let rate = summary.rate.map(|amount| Rate { amount: amount, currency: summary.currency.clone() });
+    return write_output(&args.output, &render_period_summary(&entries, timezone, summary.range.week_start, &rate));
+}
+
+async fn show_stats(args: &Cli, stats_args: &StatsArgs, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let (start, end) = resolve_range(&stats_args.range, timezone)?;
+    if !args.quiet {
+        eprintln!("Looking for entries in between: {} - {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
+    }
+    let mut entries = load_entries(args, None, client, config_path, &start, &end, timezone).await?;
+    apply_filters(&mut entries, &stats_args.filter);
+    let Some(stats) = stats::compute_stats(&entries, timezone) else {
+        println!("No shifts found for {} - {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
+        return Ok(());
+    };
+    return write_output(&args.output, &render_stats(&stats));
+}
+
+async fn show_overtime(
+    args: &Cli,
+    overtime_args: &OvertimeArgs,
+    contract: &Option<Contract>,
+    client: &ApiClient,
+    config_path: &Path,
+    timezone: &Tz,
+) -> Result<()> {
+    let Some(contract) = contract else {
+        anyhow::bail!("No contracted hours configured, add a [contract] section with hours_per_day or hours_per_week to {}", config::default_defaults_path().display());
+    };
+    let (start, end) = resolve_range(&overtime_args.range, timezone)?;
+    // Days still to come would only count as missing hours.
+    let end = end.min(Utc::now().with_timezone(timezone).date_naive());
+    if !args.quiet {
+        eprintln!("Looking for entries in between: {} - {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
+    }
+    let entries = load_entries(args, None, client, config_path, &start, &end, timezone).await?;
+    let days = overtime::overtime_by_day(&entries, timezone, contract, start, end)?;
+    return write_output(&args.output, &render_overtime(&days, overtime_args.range.week_start));
+}
+
+async fn show_earnings(
+    args: &Cli,
+    earnings_args: &EarningsArgs,
+    rates: &BTreeMap<String, ProjectRate>,
+    client: &ApiClient,
+    config_path: &Path,
+    timezone: &Tz,
+) -> Result<()> {
+    if rates.is_empty() {
+        anyhow::bail!("No rates configured, add a [rates] section like Acme = {{ rate = 80, currency = \"EUR\" }} to {}", config::default_defaults_path().display());
+    }
+    let (start, end) = resolve_range(&earnings_args.range, timezone)?;
+    if !args.quiet {
+        eprintln!("Looking for entries in between: {} - {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
+    }
+    let mut entries = load_entries(args, None, client, config_path, &start, &end, timezone).await?;
+    apply_filters(&mut entries, &earnings_args.filter);
+    let earnings = earnings::compute_earnings(&entries, rates);
+    for earning in earnings.iter().filter(|earning| earning.rate.is_none()) {
+        warn(args, format!("no rate configured for {} {}", earning.project, earning.subproject).trim_end().to_string());
+    }
+    return write_output(&args.output, &render_earnings(&earnings));
+}
+
+async fn write_invoice(args: &Cli, invoice_args: &InvoiceArgs, defaults: &Defaults, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let (start, end) = resolve_range(&invoice_args.range, timezone)?;
+    let mut entries = load_entries(args, None, client, config_path, &start, &end, timezone).await?;
+    entries.retain(|entry| matches_filter(&entry.project, &Some(invoice_args.client.clone())));
+    if entries.is_empty() {
+        anyhow::bail!("No shifts of {} in {} - {}", invoice_args.client, start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
+    }
+
+    let items = earnings::compute_earnings(&entries, &defaults.rates);
+    let unrated: Vec<String> = items.iter().filter(|item| item.rate.is_none()).map(invoice::item_label).collect();
+    if !unrated.is_empty() {
+        anyhow::bail!("No rate configured for {}, add them to the [rates] section of {}", unrated.join(", "), config::default_defaults_path().display());
+    }
+    let mut currencies: Vec<String> = items.iter().filter_map(|item| item.rate.as_ref()).map(|rate| rate.currency.clone().unwrap_or_default()).collect();
+    currencies.sort();
+    currencies.dedup();
+    if currencies.len() > 1 {
+        anyhow::bail!("The rates of {} use different currencies ({}), one invoice needs a single currency", invoice_args.client, currencies.join(", "));
+    }
+
+    let config_dir = config::default_config_dir();
+    let counter_path = invoice::invoice_counter_path(&config_dir);
+    let (number, counter) = match &invoice_args.number {
+        Some(number) => (number.clone(), None),
+        None => {
+            let counter = invoice::next_invoice_number(&counter_path).context("Failed to read the invoice counter")?;
+            (format!("{}{:04}", defaults.invoice.prefix, counter), Some(counter))
+        }
+    };
+    let invoice = invoice::Invoice {
+        number: number,
+        date: Utc::now().with_timezone(timezone).date_naive(),
+        client: invoice_args.address.clone().unwrap_or_else(|| invoice_args.client.clone()),
+        issuer: defaults.invoice.issuer.clone(),
+        start: start,
+        end: end,
+        items: items,
+        currency: currencies.pop().unwrap_or_default(),
+    };
+    let (rendered, extension) = match invoice_args.format {
+        InvoiceFormat::Text => (invoice::render_text_invoice(&invoice, &config_dir).context("Failed to read the invoice template")?.into_bytes(), "txt"),
+        InvoiceFormat::Html => (invoice::render_html_invoice(&invoice, &config_dir).context("Failed to read the invoice template")?.into_bytes(), "html"),
+        InvoiceFormat::Pdf => (pdf::render_pdf_invoice(&invoice), "pdf"),
+    };
+
+    let path = args.output.clone().unwrap_or_else(|| PathBuf::from(format!("invoice-{}.{}", invoice.number, extension)));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, rendered).with_context(|| format!("Failed to write {:?}", path))?;
+    // The number is only used up once the invoice exists.
+    if let Some(counter) = counter {
+        invoice::save_invoice_number(&counter_path, counter).with_context(|| format!("Failed to update the invoice counter {:?}", counter_path))?;
+    }
+    if !args.quiet {
+        eprintln!("Invoice {} written to {}", invoice.number, path.display());
+    }
+    return Ok(());
+}
+
+/// Moves the cursor to the top left of the cleared terminal, so the watched output is redrawn in place.
+const CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+/// Shows the timesheet once, or again and again with --watch.
+async fn watch_timesheet(args: &Cli, timesheet: &TimesheetArgs, anomalies: &AnomalyRules, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    let Some(seconds) = timesheet.watch else {
+        return show_timesheet(args, timesheet, anomalies, client, config_path, timezone).await;
+    };
+    if args.output.is_some() {
+        anyhow::bail!("--watch redraws the terminal, it can't be combined with --output");
+    }
+    loop {
+        print!("{}", CLEAR_SCREEN);
+        show_timesheet(args, timesheet, anomalies, client, config_path, timezone).await?;
+        std::io::stdout().flush()?;
+        tokio::time::sleep(std::time::Duration::from_secs(seconds)).await;
+    }
+}
+
+async fn show_timesheet(args: &Cli, timesheet: &TimesheetArgs, anomalies: &AnomalyRules, client: &ApiClient, config_path: &Path, timezone: &Tz) -> Result<()> {
+    if timesheet.tui && args.output.is_some() {
+        anyhow::bail!("--tui can't be combined with --output");
+    }
+    let (start, end) = resolve_range(&timesheet.range, timezone)?;
+    if !args.quiet {
+        eprintln!("Looking for entries in between: {} - {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
+    }
+    log::info!("Requesting range {} - {} in timezone {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"), timezone.name());
+
+    let mut entries = match &timesheet.from_file {
+        Some(path) => parse_timesheets(vec![std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?])?,
+        None if timesheet.save_response.is_none() => load_entries(args, timesheet.user, client, config_path, &start, &end, timezone).await?,
+        None => {
+            if args.offline {
+                anyhow::bail!("--save-response needs the API, it can't be combined with --offline");
+            }
+            let resps = fetch_timesheets(args, timesheet.user, client, config_path, &start, &end, timezone).await?;
+            let path = timesheet.save_response.as_ref().unwrap();
+            for (index, (_, resp)) in resps.iter().enumerate() {
+                // Additional object ids get numbered files next to the requested one.
+                let path = match index {
+                    0 => path.clone(),
+                    _ => PathBuf::from(format!("{}.{}", path.display(), index)),
+                };
+                std::fs::write(&path, resp).with_context(|| format!("Failed to save response to {:?}", path))?;
+            }
+            parse_timesheets(resps.into_iter().map(|(_, resp)| resp).collect())?
+        }
+    };
+    apply_filters(&mut entries, &timesheet.filter);
     entries.sort_by_key(|k| k.start);
-    entries.reverse();
+    for (first, second) in checks::find_overlaps(&entries) {
+        warn(args, format!("overlapping shifts {} and {}", describe_shift(&entries[first], timezone), describe_shift(&entries[second], timezone)));
+    }
+    if timesheet.dedup_overlaps {
+        checks::mark_overlaps(&mut entries, timezone);
+    }
 
-    let grouped: Vec<_> = entries
-        .group_by(|k, l| {
-            k.start.day() == l.start.day()
-                && k.start.month() == l.start.month()
-                && k.start.year() == l.start.year()
-        })
-        .collect();
+    let unrounded_total = entries.iter().fold(chrono::Duration::zero(), |total, entry| total + entry.counted_duration());
+    if let Some(minutes) = timesheet.round {
+        for entry in &mut entries {
+            entry.start = dates::round_time(entry.start, minutes, timesheet.round_mode);
+            entry.end = entry.end.map(|end| dates::round_time(end, minutes, timesheet.round_mode));
+        }
+    }
+
+    if entries.is_empty() && timesheet.format == OutputFormat::Table {
+        if timesheet.filter.project.is_some() || timesheet.filter.subproject.is_some() || timesheet.filter.grep.is_some() {
+            println!("No shifts matched the given project/subproject/description filters");
+        } else {
+            println!("No shifts found for {} - {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
+        }
+        return Ok(());
+    }
+
+    if let Some(threshold_hours) = timesheet.overtime_threshold {
+        let threshold = chrono::Duration::minutes((threshold_hours * 60.0).round() as i64);
+        for (week, total) in weekly_totals(&entries, timezone, timesheet.range.week_start) {
+            if total > threshold {
+                warn(
+                    args,
+                    format!(
+                        "week of {} has {} logged, {} over the {}h threshold",
+                        week.format("%Y-%m-%d"),
+                        format_duration(total),
+                        format_duration(total - threshold),
+                        threshold_hours
+                    ),
+                );
+            }
+        }
+    }
 
-    let mut table = term_table::Table::new();
-    table.max_column_width = 120;
-    table.style = term_table::TableStyle::extended();
-
-    table.add_row(Row::new(vec![
-        TableCell::new("Start"),
-        TableCell::new("End"),
-        TableCell::new("Description"),
-        TableCell::new("Project"),
-        TableCell::new("Subproject"),
-    ]));
-    for day in grouped {
-        table.add_row(Row::new(vec![TableCell::new_with_alignment(
-            day.first().unwrap().start.date_naive(),
-            5,
-            term_table::table_cell::Alignment::Center,
-        )]));
-        for entry in day {
-            table.add_row(Row::new(vec![
-                TableCell::new(entry.start.time().format("%H:%M")),
-                TableCell::new(entry.end.time().format("%H:%M")),
-                TableCell::new(&entry.desc),
-                TableCell::new(&entry.project),
-                TableCell::new(&entry.subproject),
-            ]));
-        }
-    }
-    println!("{}", table.render());
-}
-
-#[derive(Parser)]
-struct Cli {
-    #[clap(short, long, default_value = "7 days ago")]
-    start: String,
-
-    #[clap(short, long, default_value = "today")]
-    end: String,
-}
-
-fn main() -> Result<()> {
-    let args = Cli::parse();
-    let start = parse_date_string(&args.start, Local::now(), Dialect::Uk).with_context(|| f!("Failed to parse start date: {args.start}"))?;
-    let end = parse_date_string(&args.end, Local::now(), Dialect::Uk).with_context(|| f!("Failed to parse end date: {args.end}"))?;
-    println!("Looking for entries in between: {} - {}", start.format("%Y-%m-%d"), end.format("%Y-%m-%d"));
-
-    let session_info = load_session_info_or_ask_user().with_context(|| "Failed to load session")?;
-
-    let resp = send_request_get_timesheet(&session_info, &start, &end).with_context(|| "Failed to send request \"get timesheet\"")?;
-    let mut entries = parse_timesheet(resp).with_context(|| "Failed to parse request")?;
-
-    draw_timesheet(&mut entries);
+    if timesheet.tui {
+        entries.sort_by_key(|k| k.start);
+        if timesheet.sort == SortOrder::Desc {
+            entries.reverse();
+        }
+        return tui::run(&entries, timezone).context("Failed to run the terminal UI");
+    }
+
+    let table_width = timesheet.width.or_else(|| match terminal_size::terminal_size() {
+        Some((terminal_size::Width(width), _)) if args.output.is_none() => Some(width as usize),
+        _ => None,
+    });
+    let rate = timesheet.rate.map(|amount| Rate { amount: amount, currency: timesheet.currency.clone() });
+    let mut rendered = match timesheet.format {
+        OutputFormat::Table => match &timesheet.summary {
+            Some(summary) => render_summary(&entries, summary, &rate),
+            None => render_timesheet(&mut entries, timezone, &timesheet.sort, &rate, table_width, &timesheet.group_by, anomalies),
+        },
+        OutputFormat::Csv => {
+            entries.sort_by_key(|k| k.start);
+            render_csv(&entries, timezone)?
+        }
+        OutputFormat::Json => {
+            entries.sort_by_key(|k| k.start);
+            serde_json::to_string_pretty(&entries)? + "\n"
+        }
+        OutputFormat::Ics => export_ics(&entries),
+        OutputFormat::Markdown => render_markdown(&mut entries, timezone, &timesheet.sort, &rate, &timesheet.group_by),
+        OutputFormat::Html => render_html(&mut entries, timezone, &timesheet.sort, &rate, &timesheet.group_by),
+    };
+    if timesheet.round.is_some() && matches!(timesheet.format, OutputFormat::Table | OutputFormat::Markdown) {
+        rendered += &format!("Total before rounding: {}\n", format_duration(unrounded_total));
+    }
+    write_output(&args.output, &rendered)?;
     Ok(())
 }
@@ -0,0 +1,164 @@
+//! Typed shapes of the API responses. Fields the client relies on are required, so schema changes fail loudly.
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct Response<T> {
+    pub data: T,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ContentStructure {
+    /// Missing when the session has expired, the response then has no dashboard content.
+    pub containers: Option<Vec<Container>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Container {
+    pub name: String,
+
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Asset {
+    #[serde(rename = "dashboardType")]
+    pub dashboard_type: Option<String>,
+
+    #[serde(default)]
+    pub courses: Vec<Course>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Course {
+    #[serde(default)]
+    pub sections: Vec<Section>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Section {
+    #[serde(default)]
+    pub objects: Vec<ContentObject>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ContentObject {
+    pub id: u64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Timesheet {
+    #[serde(rename = "userTimeSheets")]
+    pub user_time_sheets: UserTimeSheets,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UserTimeSheets {
+    #[serde(rename = "timeSheetEntries")]
+    pub time_sheet_entries: Vec<TimeSheetEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TimeSheetEntry {
+    #[serde(rename = "timeSheetDayEntries")]
+    pub time_sheet_day_entries: Vec<TimeSheetDayEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TimeSheetDayEntry {
+    pub shifts: Vec<Shift>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Shift {
+    /// Missing in older responses, such shifts can't be edited.
+    #[serde(default)]
+    pub id: String,
+
+    #[serde(rename = "punchIn")]
+    pub punch_in: Punch,
+
+    /// `None` or a zero timestamp while the shift is running.
+    #[serde(rename = "punchOut")]
+    pub punch_out: Option<Punch>,
+
+    #[serde(rename = "shiftAttachments")]
+    pub shift_attachments: Vec<ShiftAttachment>,
+
+    #[serde(rename = "employeeNotes")]
+    pub employee_notes: Option<String>,
+
+    #[serde(rename = "punchTag")]
+    pub punch_tag: PunchTag,
+
+    #[serde(default)]
+    pub breaks: Vec<Break>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Punch {
+    #[serde(rename = "timestampWithTimezone")]
+    pub timestamp_with_timezone: TimestampWithTimezone,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TimestampWithTimezone {
+    /// Seconds since the epoch.
+    pub timestamp: i64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShiftAttachment {
+    #[serde(rename = "freeText")]
+    pub free_text: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PunchTag {
+    pub name: String,
+
+    #[serde(rename = "subItems", default)]
+    pub sub_items: Vec<PunchTag>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Break {
+    /// `None` while the break is running.
+    pub start: Option<Punch>,
+    pub end: Option<Punch>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PunchclockData {
+    #[serde(rename = "shiftAttachments")]
+    pub shift_attachments: Vec<AttachmentSettings>,
+
+    #[serde(rename = "availableTags")]
+    pub available_tags: Vec<AvailableTag>,
+
+    #[serde(rename = "activeShift")]
+    pub active_shift: Option<Shift>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AttachmentSettings {
+    pub id: String,
+
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    pub title: String,
+
+    #[serde(rename = "isRequired", default)]
+    pub is_required: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AvailableTag {
+    pub id: u64,
+    pub name: String,
+
+    #[serde(rename = "subItems", default)]
+    pub sub_items: Vec<AvailableTag>,
+}
@@ -0,0 +1,73 @@
+use chrono::{Datelike, NaiveDate};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+
+use crate::config::Contract;
+use crate::error::ConnecteamError;
+use crate::parse::TimesheetEntry;
+
+/// Worked and contracted time of one day, `cumulative` is the overtime of the period up to and including it.
+#[derive(Debug, PartialEq)]
+pub struct OvertimeDay {
+    pub date: NaiveDate,
+    pub worked: chrono::Duration,
+    pub contracted: chrono::Duration,
+    pub cumulative: chrono::Duration,
+}
+
+impl OvertimeDay {
+    pub fn delta(&self) -> chrono::Duration {
+        return self.worked - self.contracted;
+    }
+}
+
+/// Every day from `start` to `end`, including the days off and the days without shifts.
+pub fn overtime_by_day(
+    entries: &[TimesheetEntry],
+    timezone: &Tz,
+    contract: &Contract,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<Vec<OvertimeDay>, ConnecteamError> {
+    let mut worked: BTreeMap<NaiveDate, chrono::Duration> = BTreeMap::new();
+    for entry in entries {
+        let total = worked.entry(entry.start.with_timezone(timezone).date_naive()).or_insert(chrono::Duration::zero());
+        *total = *total + entry.net_duration();
+    }
+
+    let mut days = vec![];
+    let mut cumulative = chrono::Duration::zero();
+    for date in start.iter_days().take_while(|date| *date <= end) {
+        let worked = worked.get(&date).copied().unwrap_or(chrono::Duration::zero());
+        let contracted = contract.hours_on(date.weekday())?;
+        cumulative = cumulative + worked - contracted;
+        days.push(OvertimeDay { date: date, worked: worked, contracted: contracted, cumulative: cumulative });
+    }
+    return Ok(days);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn shift(day: u32, hours: i64) -> TimesheetEntry {
+        let start = Utc.with_ymd_and_hms(2023, 10, day, 8, 0, 0).unwrap();
+        return TimesheetEntry::test_shift(start, Some(start + chrono::Duration::hours(hours)));
+    }
+
+    #[test]
+    fn accumulates_overtime() {
+        let contract = Contract { hours_per_day: Some(8.0), hours_per_week: None, workdays: vec![chrono::Weekday::Mon, chrono::Weekday::Tue] };
+        // 2023-10-02 is a Monday, nothing was logged on Tuesday and Wednesday is a day off.
+        let entries = vec![shift(2, 10), shift(4, 2)];
+        let day = |day: u32| NaiveDate::from_ymd_opt(2023, 10, day).unwrap();
+        let days = overtime_by_day(&entries, &chrono_tz::UTC, &contract, day(2), day(4)).unwrap();
+
+        assert_eq!(days.len(), 3);
+        assert_eq!(days[0].delta(), chrono::Duration::hours(2));
+        assert_eq!(days[1].delta(), chrono::Duration::hours(-8));
+        assert_eq!(days[2].contracted, chrono::Duration::zero());
+        assert_eq!(days[2].cumulative, chrono::Duration::hours(-4));
+    }
+}
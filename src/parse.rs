@@ -0,0 +1,446 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::ConnecteamError;
+use crate::json_ext::parse_response;
+use crate::models::{AvailableTag, Punch, PunchclockData, Response, Shift, Timesheet};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TimesheetEntry {
+    /// Id of the shift in Connecteam, used by `shift edit`. Empty for shifts that were not fetched.
+    #[serde(default)]
+    pub id: String,
+    pub start: DateTime<Utc>,
+    /// `None` while the shift has not been punched out yet.
+    pub end: Option<DateTime<Utc>>,
+    pub desc: String,
+    /// Employee notes alone, `desc` also has the attachment texts. `shift edit` sends only these back.
+    #[serde(default)]
+    pub notes: String,
+    pub project: String,
+    pub subproject: String,
+    pub breaks: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+    /// Time already covered by an earlier shift of the same day, left out of the totals by `--dedup-overlaps`.
+    #[serde(skip, default = "chrono::Duration::zero")]
+    pub overlap: chrono::Duration,
+}
+
+impl TimesheetEntry {
+    /// Time between punch in and punch out, breaks included. Shifts still in progress count as zero.
+    pub fn duration(&self) -> chrono::Duration {
+        return match self.end {
+            Some(end) => end - self.start,
+            None => chrono::Duration::zero(),
+        };
+    }
+
+    pub fn break_duration(&self) -> chrono::Duration {
+        return self
+            .breaks
+            .iter()
+            .fold(chrono::Duration::zero(), |total, (start, end)| total + (*end - *start));
+    }
+
+    /// Time actually worked, i.e. the duration without breaks. Like `duration`, zero while in progress.
+    pub fn net_duration(&self) -> chrono::Duration {
+        return match self.end {
+            Some(_) => self.duration() - self.break_duration(),
+            None => chrono::Duration::zero(),
+        };
+    }
+
+    /// Worked time the totals count, i.e. `net_duration` without the `overlap`.
+    pub fn counted_duration(&self) -> chrono::Duration {
+        return (self.net_duration() - self.overlap).max(chrono::Duration::zero());
+    }
+}
+
+/// Shifts for the tests of the modules working on timesheets.
+#[cfg(test)]
+impl TimesheetEntry {
+    /// Shift on "Acme" without id, description, subproject or breaks.
+    pub fn test_shift(start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> TimesheetEntry {
+        return TimesheetEntry {
+            id: "".to_string(),
+            start: start,
+            end: end,
+            desc: "".to_string(),
+            notes: "".to_string(),
+            project: "Acme".to_string(),
+            subproject: "".to_string(),
+            breaks: vec![],
+            overlap: chrono::Duration::zero(),
+        };
+    }
+
+    pub fn on_project(mut self, project: &str, subproject: &str) -> TimesheetEntry {
+        self.project = project.to_string();
+        self.subproject = subproject.to_string();
+        return self;
+    }
+
+    pub fn with_breaks(mut self, breaks: Vec<(DateTime<Utc>, DateTime<Utc>)>) -> TimesheetEntry {
+        self.breaks = breaks;
+        return self;
+    }
+}
+
+/// Converts a punch into a time, zero timestamps mean the punch did not happen yet.
+fn punch_time(punch: &Punch) -> Option<DateTime<Utc>> {
+    let seconds_since_epoch = punch.timestamp_with_timezone.timestamp;
+    if seconds_since_epoch <= 0 {
+        return None;
+    }
+    return Utc.timestamp_opt(seconds_since_epoch, 0).single();
+}
+
+fn parse_shift(shift: &Shift) -> Result<TimesheetEntry, ConnecteamError> {
+    // Free texts of all attachments followed by the notes, skipping null and empty values.
+    let mut desc_parts: Vec<&str> = shift
+        .shift_attachments
+        .iter()
+        .filter_map(|attachment| attachment.free_text.as_deref())
+        .collect();
+    let notes = shift.employee_notes.clone().unwrap_or_default();
+    desc_parts.extend(shift.employee_notes.as_deref());
+    desc_parts.retain(|part| !part.is_empty());
+    let desc = desc_parts.join(" / ");
+
+    let start = punch_time(&shift.punch_in).ok_or_else(|| ConnecteamError::Parse("shift without punch in timestamp".to_string()))?;
+
+    return Ok(TimesheetEntry {
+        id: shift.id.clone(),
+        start: start,
+        end: shift.punch_out.as_ref().and_then(punch_time),
+        desc: desc,
+        notes: notes,
+        project: shift.punch_tag.name.clone(),
+        subproject: shift
+            .punch_tag
+            .sub_items
+            .first()
+            .map(|sub_item| sub_item.name.clone())
+            .unwrap_or_default(),
+        breaks: shift
+            .breaks
+            .iter()
+            // Breaks that are still running are left out until they end.
+            .filter_map(|shift_break| Some((punch_time(shift_break.start.as_ref()?)?, punch_time(shift_break.end.as_ref()?)?)))
+            .collect(),
+        overlap: chrono::Duration::zero(),
+    });
+}
+
+pub fn parse_timesheet(resp: String) -> Result<Vec<TimesheetEntry>, ConnecteamError> {
+    let parsed: Response<Timesheet> = parse_response(&resp, "PunchClock/Timesheet")?;
+
+    let timesheet_entries = parsed
+        .data
+        .user_time_sheets
+        .time_sheet_entries
+        .iter()
+        .flat_map(|x| &x.time_sheet_day_entries)
+        .flat_map(|x| &x.shifts)
+        .map(parse_shift)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    return Ok(timesheet_entries);
+}
+
+#[derive(Serialize, Debug)]
+pub struct Tag {
+    pub id: u64,
+    pub name: String,
+    pub sub_items: Vec<Tag>,
+}
+
+fn parse_tag(tag: &AvailableTag) -> Tag {
+    return Tag {
+        id: tag.id,
+        name: tag.name.clone(),
+        sub_items: tag.sub_items.iter().map(parse_tag).collect(),
+    };
+}
+
+#[derive(Serialize, Debug)]
+pub struct Attachment {
+    pub id: String,
+    pub kind: String,
+    pub title: String,
+    pub required: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct PunchclockMeta {
+    pub attachments: Vec<Attachment>,
+    pub tags: Vec<Tag>,
+    /// The shift that is currently punched in, `None` when clocked out.
+    pub active_shift: Option<TimesheetEntry>,
+    /// Notes of the active shift alone, its `desc` also has the attachment texts. Empty when clocked out.
+    #[serde(skip)]
+    pub active_shift_notes: String,
+}
+
+pub fn parse_punchclock(resp: String) -> Result<PunchclockMeta, ConnecteamError> {
+    let parsed: Response<PunchclockData> = parse_response(&resp, "PunchClock/Data")?;
+    let attachments = parsed
+        .data
+        .shift_attachments
+        .iter()
+        .map(|attachment| Attachment {
+            id: attachment.id.clone(),
+            kind: attachment.kind.clone(),
+            title: attachment.title.clone(),
+            required: attachment.is_required,
+        })
+        .collect();
+    let tags = parsed.data.available_tags.iter().map(parse_tag).collect();
+    let active_shift = parsed.data.active_shift.as_ref().map(parse_shift).transpose()?;
+    let active_shift_notes = parsed
+        .data
+        .active_shift
+        .as_ref()
+        .and_then(|shift| shift.employee_notes.clone())
+        .unwrap_or_default();
+    return Ok(PunchclockMeta {
+        attachments: attachments,
+        tags: tags,
+        active_shift: active_shift,
+        active_shift_notes: active_shift_notes,
+    });
+}
+
+fn find_tag<'a>(tags: &'a [Tag], name: &str) -> Result<&'a Tag, ConnecteamError> {
+    return tags
+        .iter()
+        .find(|tag| tag.name.to_lowercase() == name.to_lowercase())
+        .ok_or_else(|| {
+            let available = tags.iter().map(|tag| tag.name.clone()).collect::<Vec<_>>().join(", ");
+            ConnecteamError::UnknownTag(f!("{name} (available: {available})"))
+        });
+}
+
+/// Looks up the tag ids for a project and optional subproject by name (case-insensitive).
+pub fn find_tag_ids(tags: &[Tag], project: &str, subproject: &Option<String>) -> Result<(u64, Option<u64>), ConnecteamError> {
+    let tag = find_tag(tags, project)?;
+    let sub_tag_id = match subproject {
+        Some(subproject) => Some(find_tag(&tag.sub_items, subproject)?.id),
+        None => None,
+    };
+    return Ok((tag.id, sub_tag_id));
+}
+
+/// How well `name` matches `query`, ignoring case: 3 equal, 2 containing it, 1 with its characters in order, 0 not at all.
+fn match_quality(name: &str, query: &str) -> u8 {
+    let (name, query) = (name.to_lowercase(), query.trim().to_lowercase());
+    if name == query {
+        return 3;
+    }
+    if name.contains(&query) {
+        return 2;
+    }
+    let mut name_chars = name.chars();
+    if query.chars().all(|query_char| name_chars.any(|name_char| name_char == query_char)) {
+        return 1;
+    }
+    return 0;
+}
+
+/// Resolves a loosely typed "project/subproject", e.g. "acme/back", to the best matching tags. Without a slash the
+/// projects are tried first and then the subprojects on their own, with an empty subproject like "acme/" only the
+/// projects. Several equally good matches are an error.
+pub fn resolve_tag<'a>(tags: &'a [Tag], query: &str) -> Result<(&'a Tag, Option<&'a Tag>), ConnecteamError> {
+    let mut candidates: Vec<(u8, &Tag, Option<&Tag>)> = vec![];
+    match query.split_once('/') {
+        // Every subproject would match the empty part equally well, the project itself is meant.
+        Some((project, subproject)) if subproject.trim().is_empty() => {
+            candidates.extend(tags.iter().map(|tag| (match_quality(&tag.name, project), tag, None)));
+        }
+        Some((project, subproject)) => {
+            for tag in tags {
+                let project_quality = match_quality(&tag.name, project);
+                for sub_item in &tag.sub_items {
+                    candidates.push((project_quality.min(match_quality(&sub_item.name, subproject)), tag, Some(sub_item)));
+                }
+            }
+        }
+        None => {
+            candidates.extend(tags.iter().map(|tag| (match_quality(&tag.name, query), tag, None)));
+            if candidates.iter().all(|(quality, _, _)| *quality == 0) {
+                for tag in tags {
+                    candidates.extend(tag.sub_items.iter().map(|sub_item| (match_quality(&sub_item.name, query), tag, Some(sub_item))));
+                }
+            }
+        }
+    }
+
+    let best = candidates.iter().map(|(quality, _, _)| *quality).max().unwrap_or(0);
+    if best == 0 {
+        let available = tags.iter().map(|tag| tag.name.clone()).collect::<Vec<_>>().join(", ");
+        return Err(ConnecteamError::UnknownTag(f!("{query} (available: {available})")));
+    }
+    let matches: Vec<(&Tag, Option<&Tag>)> =
+        candidates.into_iter().filter(|(quality, _, _)| *quality == best).map(|(_, tag, sub_item)| (tag, sub_item)).collect();
+    if matches.len() > 1 {
+        let names: Vec<String> = matches
+            .iter()
+            .map(|(tag, sub_item)| match sub_item {
+                Some(sub_item) => format!("{} / {}", tag.name, sub_item.name),
+                None => tag.name.clone(),
+            })
+            .collect();
+        return Err(ConnecteamError::AmbiguousTag(format!("{} matches {}", query, names.join(", "))));
+    }
+    return Ok(matches[0]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_fixture(fixture: &str) -> Vec<TimesheetEntry> {
+        return parse_timesheet(fixture.to_string()).unwrap();
+    }
+
+    #[test]
+    fn parses_every_shift() {
+        let entries = parse_fixture(include_str!("../tests/fixtures/timesheet.json"));
+        assert_eq!(entries.len(), 3);
+    }
+
+    #[test]
+    fn parses_punch_timestamps() {
+        let entries = parse_fixture(include_str!("../tests/fixtures/timesheet.json"));
+        assert_eq!(entries[0].start, Utc.with_ymd_and_hms(2023, 10, 2, 7, 0, 0).unwrap());
+        assert_eq!(entries[0].end, Some(Utc.with_ymd_and_hms(2023, 10, 2, 11, 30, 0).unwrap()));
+        assert_eq!(entries[2].start, Utc.with_ymd_and_hms(2023, 10, 3, 6, 30, 0).unwrap());
+    }
+
+    #[test]
+    fn combines_free_text_and_notes() {
+        let entries = parse_fixture(include_str!("../tests/fixtures/timesheet.json"));
+        assert_eq!(entries[0].desc, "Reviewed PR #42 / pairing session");
+        assert_eq!(entries[0].notes, "pairing session");
+        assert_eq!(entries[1].notes, "");
+        assert_eq!(entries[1].desc, "Release planning");
+        assert_eq!(entries[2].desc, "on-call handover");
+    }
+
+    #[test]
+    fn parses_project_and_breaks() {
+        let entries = parse_fixture(include_str!("../tests/fixtures/timesheet.json"));
+        assert_eq!(entries[2].project, "Globex");
+        assert_eq!(entries[2].subproject, "Support");
+        assert_eq!(entries[2].break_duration(), chrono::Duration::minutes(30));
+    }
+
+    #[test]
+    fn computes_net_duration_without_breaks() {
+        let entries = parse_fixture(include_str!("../tests/fixtures/timesheet.json"));
+        assert_eq!(entries[0].duration(), chrono::Duration::minutes(270));
+        assert_eq!(entries[2].net_duration(), entries[2].duration() - chrono::Duration::minutes(30));
+    }
+
+    #[test]
+    fn keeps_shift_without_punch_out_open() {
+        let entries = parse_fixture(include_str!("../tests/fixtures/timesheet_open_shift.json"));
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].end, None);
+        assert_eq!(entries[0].duration(), chrono::Duration::zero());
+        assert_eq!(entries[1].end, None);
+    }
+
+    #[test]
+    fn counts_running_shift_with_break_as_zero() {
+        let start = Utc.with_ymd_and_hms(2023, 10, 2, 7, 0, 0).unwrap();
+        let entry = TimesheetEntry::test_shift(start, None).with_breaks(vec![(start + chrono::Duration::hours(2), start + chrono::Duration::minutes(150))]);
+        assert_eq!(entry.break_duration(), chrono::Duration::minutes(30));
+        assert_eq!(entry.net_duration(), chrono::Duration::zero());
+    }
+
+    #[test]
+    fn keeps_literal_null_text() {
+        let entries = parse_fixture(include_str!("../tests/fixtures/timesheet_null_text.json"));
+        assert_eq!(entries[0].desc, "null");
+        assert_eq!(entries[1].desc, "");
+    }
+
+    #[test]
+    fn names_endpoint_in_parse_errors() {
+        let err = parse_timesheet("<html>Bad gateway</html>".to_string()).err().unwrap();
+        assert!(err.to_string().contains("PunchClock/Timesheet"));
+        assert!(err.to_string().contains("<html>Bad gateway"));
+    }
+
+    #[test]
+    fn reports_missing_fields() {
+        let err = parse_timesheet(r#"{"data": {"userTimeSheets": {}}}"#.to_string()).err().unwrap();
+        assert!(err.to_string().contains("missing field `timeSheetEntries`"));
+    }
+
+    #[test]
+    fn handles_shift_without_attachments() {
+        let entries = parse_fixture(include_str!("../tests/fixtures/timesheet_no_attachments.json"));
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].desc, "quick punch");
+    }
+
+    #[test]
+    fn handles_tag_without_sub_items() {
+        let entries = parse_fixture(include_str!("../tests/fixtures/timesheet_no_subitems.json"));
+        assert_eq!(entries[0].project, "Warehouse");
+        assert_eq!(entries[0].subproject, "");
+    }
+
+    fn parse_punchclock_fixture() -> PunchclockMeta {
+        return parse_punchclock(include_str!("../tests/fixtures/punchclock_data.json").to_string()).unwrap();
+    }
+
+    #[test]
+    fn parses_punchclock_attachments_and_tags() {
+        let meta = parse_punchclock_fixture();
+        assert_eq!(meta.attachments.len(), 1);
+        assert_eq!(meta.attachments[0].kind, "freeText");
+        assert!(!meta.attachments[0].required);
+        assert_eq!(meta.tags.len(), 3);
+        assert_eq!(meta.tags[0].sub_items[1].name, "Meetings");
+    }
+
+    #[test]
+    fn resolves_tag_ids_by_name() {
+        let tags = parse_punchclock_fixture().tags;
+        assert_eq!(find_tag_ids(&tags, "globex", &Some("support".to_string())).unwrap(), (2, Some(21)));
+        assert_eq!(find_tag_ids(&tags, "Warehouse", &None).unwrap(), (3, None));
+        assert!(find_tag_ids(&tags, "Initech", &None).is_err());
+    }
+
+    #[test]
+    fn resolves_tags_fuzzily() {
+        let tags = parse_punchclock_fixture().tags;
+        let resolve = |query: &str| resolve_tag(&tags, query).map(|(tag, sub_item)| (tag.id, sub_item.map(|sub_item| sub_item.id)));
+        assert_eq!(resolve("acme/dev").unwrap(), (1, Some(11)));
+        assert_eq!(resolve("gbx / sup").unwrap(), (2, Some(21)));
+        assert_eq!(resolve("ware").unwrap(), (3, None));
+        assert_eq!(resolve("meet").unwrap(), (1, Some(12)));
+        assert!(matches!(resolve("acme/e"), Err(ConnecteamError::AmbiguousTag(_))));
+        // An empty subproject picks the project, with or without subprojects.
+        assert_eq!(resolve("acme/").unwrap(), (1, None));
+        assert_eq!(resolve("ware / ").unwrap(), (3, None));
+        assert!(matches!(resolve("warehouse/dev"), Err(ConnecteamError::UnknownTag(_))));
+        assert!(matches!(resolve("initech"), Err(ConnecteamError::UnknownTag(_))));
+    }
+
+    #[test]
+    fn parses_active_shift() {
+        assert!(parse_punchclock_fixture().active_shift.is_none());
+
+        let meta = parse_punchclock(include_str!("../tests/fixtures/punchclock_data_clocked_in.json").to_string()).unwrap();
+        let shift = meta.active_shift.unwrap();
+        assert_eq!(shift.start, Utc.with_ymd_and_hms(2023, 10, 2, 7, 0, 0).unwrap());
+        assert_eq!(shift.end, None);
+        assert_eq!(shift.project, "Acme");
+        assert_eq!(shift.subproject, "Development");
+        assert_eq!(shift.desc, "Sprint 12 / code review");
+        assert_eq!(meta.active_shift_notes, "code review");
+    }
+}
@@ -0,0 +1,226 @@
+use chrono::{Datelike, NaiveDate};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+
+use crate::invoice::{item_label, Invoice};
+use crate::parse::TimesheetEntry;
+use crate::render::format_duration;
+
+// A4 in points, the built-in Helvetica font needs no embedding.
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 50.0;
+const LINE_HEIGHT: f32 = 15.0;
+const FONT_SIZE: f32 = 10.0;
+
+/// Escapes text for a PDF string literal, characters outside Latin-1 are replaced with `?`.
+fn escape_pdf_text(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' '..='~' => escaped.push(c),
+            // WinAnsiEncoding matches Latin-1 for these.
+            '\u{a0}'..='\u{ff}' => escaped.push_str(&format!("\\{:03o}", c as u32)),
+            _ => escaped.push('?'),
+        }
+    }
+    return escaped;
+}
+
+/// Content streams of the pages, filled from the top of the first page downwards.
+struct Pages {
+    pages: Vec<String>,
+    y: f32,
+}
+
+impl Pages {
+    fn new() -> Pages {
+        return Pages { pages: vec![String::new()], y: PAGE_HEIGHT - MARGIN };
+    }
+
+    /// Starts a new page unless `lines` more lines fit on the current one.
+    fn reserve(&mut self, lines: usize) {
+        if self.y - lines as f32 * LINE_HEIGHT < MARGIN {
+            self.pages.push(String::new());
+            self.y = PAGE_HEIGHT - MARGIN;
+        }
+    }
+
+    fn text(&mut self, x: f32, size: f32, text: &str) {
+        let page = self.pages.last_mut().unwrap();
+        page.push_str(&format!("BT /F1 {} Tf {} {} Td ({}) Tj ET\n", size, x, self.y, escape_pdf_text(text)));
+    }
+
+    fn row(&mut self, columns: &[(f32, String)]) {
+        self.reserve(1);
+        for (x, text) in columns {
+            self.text(*x, FONT_SIZE, text);
+        }
+        self.y -= LINE_HEIGHT;
+    }
+
+    fn rule(&mut self, from: f32, to: f32) {
+        let page = self.pages.last_mut().unwrap();
+        page.push_str(&format!("{} {} m {} {} l S\n", from, self.y + LINE_HEIGHT - 4.0, to, self.y + LINE_HEIGHT - 4.0));
+    }
+}
+
+/// Serializes the page content streams into a PDF document with a cross-reference table.
+fn write_pdf(pages: &[String]) -> Vec<u8> {
+    // Objects 1-3 are the catalog, the page tree and the font, each page adds a page and a content object.
+    let page_ids: Vec<usize> = (0..pages.len()).map(|index| 4 + 2 * index).collect();
+    let kids = page_ids.iter().map(|id| format!("{} 0 R", id)).collect::<Vec<_>>().join(" ");
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids, pages.len()),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+    ];
+    for (page, id) in pages.iter().zip(&page_ids) {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            id + 1
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}endstream", page.len(), page));
+    }
+
+    let mut pdf = "%PDF-1.4\n".to_string();
+    let mut offsets = vec![];
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", index + 1, object));
+    }
+    let xref_offset = pdf.len();
+    pdf.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+    for offset in offsets {
+        pdf.push_str(&format!("{:010} 00000 n \n", offset));
+    }
+    pdf.push_str(&format!(
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref_offset
+    ));
+    return pdf.into_bytes();
+}
+
+/// Printable timesheet of a period with one row per day, the total and a signature line.
+pub fn render_pdf_report(entries: &[TimesheetEntry], timezone: &Tz, title: &str, employee: &Option<String>) -> Vec<u8> {
+    let mut days: BTreeMap<NaiveDate, Vec<&TimesheetEntry>> = BTreeMap::new();
+    for entry in entries {
+        days.entry(entry.start.with_timezone(timezone).date_naive()).or_default().push(entry);
+    }
+
+    let columns = [MARGIN, MARGIN + 85.0, MARGIN + 135.0, MARGIN + 185.0, MARGIN + 235.0, MARGIN + 285.0];
+    let mut pages = Pages::new();
+    pages.text(MARGIN, 16.0, title);
+    pages.y -= 2.0 * LINE_HEIGHT;
+    if let Some(employee) = employee {
+        pages.row(&[(MARGIN, f!("Employee: {employee}"))]);
+    }
+    pages.y -= LINE_HEIGHT;
+
+    let header = ["Date", "Start", "End", "Break", "Hours", "Projects"];
+    pages.row(&columns.iter().zip(header).map(|(x, text)| (*x, text.to_string())).collect::<Vec<_>>());
+    pages.rule(MARGIN, PAGE_WIDTH - MARGIN);
+
+    let mut grand_total = chrono::Duration::zero();
+    for (date, day_entries) in &days {
+        let start = day_entries.iter().map(|entry| entry.start).min().unwrap();
+        let end = day_entries.iter().filter_map(|entry| entry.end).max();
+        let break_total = day_entries.iter().fold(chrono::Duration::zero(), |total, entry| total + entry.break_duration());
+        let day_total = day_entries.iter().fold(chrono::Duration::zero(), |total, entry| total + entry.net_duration());
+        let mut projects: Vec<&str> = day_entries.iter().map(|entry| entry.project.as_str()).collect();
+        projects.sort();
+        projects.dedup();
+        grand_total = grand_total + day_total;
+
+        pages.row(&[
+            (columns[0], format!("{} {}", date.format("%Y-%m-%d"), date.weekday())),
+            (columns[1], start.with_timezone(timezone).format("%H:%M").to_string()),
+            (columns[2], end.map(|end| end.with_timezone(timezone).format("%H:%M").to_string()).unwrap_or_default()),
+            (columns[3], format_duration(break_total)),
+            (columns[4], format_duration(day_total)),
+            (columns[5], projects.join(", ")),
+        ]);
+    }
+    pages.rule(MARGIN, PAGE_WIDTH - MARGIN);
+    pages.row(&[(columns[0], "Total".to_string()), (columns[4], format_duration(grand_total))]);
+
+    // Keep the signature lines together at the end of the last page.
+    pages.reserve(5);
+    pages.y -= 3.0 * LINE_HEIGHT;
+    pages.rule(MARGIN, MARGIN + 200.0);
+    pages.rule(PAGE_WIDTH - MARGIN - 200.0, PAGE_WIDTH - MARGIN);
+    pages.row(&[(MARGIN, "Employee signature".to_string()), (PAGE_WIDTH - MARGIN - 200.0, "Manager signature".to_string())]);
+
+    return write_pdf(&pages.pages);
+}
+
+/// Invoice with the sender, the client, one line per project and the total.
+pub fn render_pdf_invoice(invoice: &Invoice) -> Vec<u8> {
+    let columns = [MARGIN, MARGIN + 280.0, MARGIN + 340.0, MARGIN + 420.0];
+    let money = |amount: f64| format!("{:.2} {}", amount, invoice.currency).trim_end().to_string();
+    let mut pages = Pages::new();
+    pages.text(MARGIN, 16.0, &f!("Invoice {invoice.number}"));
+    pages.y -= 2.0 * LINE_HEIGHT;
+    pages.row(&[(MARGIN, format!("Date: {}", invoice.date.format("%Y-%m-%d")))]);
+    pages.row(&[(MARGIN, format!("Period: {}", invoice.period()))]);
+    pages.y -= LINE_HEIGHT;
+
+    for (title, text) in [("From", &invoice.issuer), ("To", &invoice.client)] {
+        pages.row(&[(MARGIN, title.to_string())]);
+        for line in text.lines() {
+            pages.row(&[(MARGIN, line.to_string())]);
+        }
+        pages.y -= LINE_HEIGHT;
+    }
+
+    let header = ["Project", "Hours", "Rate", "Amount"];
+    pages.row(&columns.iter().zip(header).map(|(x, text)| (*x, text.to_string())).collect::<Vec<_>>());
+    pages.rule(MARGIN, PAGE_WIDTH - MARGIN);
+    for item in &invoice.items {
+        pages.row(&[
+            (columns[0], item_label(item)),
+            (columns[1], format_duration(item.duration)),
+            (columns[2], money(item.rate.as_ref().map(|rate| rate.rate).unwrap_or_default())),
+            (columns[3], money(item.amount().unwrap_or_default())),
+        ]);
+    }
+    pages.rule(MARGIN, PAGE_WIDTH - MARGIN);
+    pages.row(&[(columns[0], "Total".to_string()), (columns[3], money(invoice.total()))]);
+    return write_pdf(&pages.pages);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_pdf_text() {
+        assert_eq!(escape_pdf_text("Acme (backend)"), "Acme \\(backend\\)");
+        assert_eq!(escape_pdf_text("Zürich"), "Z\\374rich");
+        assert_eq!(escape_pdf_text("Łódź"), "?\\363d?");
+    }
+
+    #[test]
+    fn points_xref_at_objects() {
+        let pdf = String::from_utf8(write_pdf(&["BT ET\n".to_string(), "BT ET\n".to_string()])).unwrap();
+        let xref_offset: usize = pdf.lines().rev().nth(1).unwrap().parse().unwrap();
+        assert!(pdf[xref_offset..].starts_with("xref\n0 8\n"));
+
+        let offsets: Vec<usize> = pdf[xref_offset..]
+            .lines()
+            .skip(3)
+            .take(7)
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        for (index, offset) in offsets.iter().enumerate() {
+            assert!(pdf[*offset..].starts_with(&format!("{} 0 obj", index + 1)));
+        }
+    }
+}
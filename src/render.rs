@@ -0,0 +1,843 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use chrono::{DateTime, Datelike, NaiveDate, Utc, Weekday};
+use colored::Colorize;
+use chrono_tz::Tz;
+use term_table::row::Row;
+use term_table::table_cell::TableCell;
+
+use crate::checks::{find_anomalies, Anomaly};
+use crate::config::AnomalyRules;
+use crate::dates::{start_of_week, WeekStart};
+use crate::earnings::{totals_by_currency, Earning};
+use crate::overtime::OvertimeDay;
+use crate::parse::{Tag, TimesheetEntry};
+use crate::stats::Stats;
+
+#[derive(clap::ValueEnum, Clone, PartialEq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Hourly rate used to estimate pay from the worked hours.
+pub struct Rate {
+    pub amount: f64,
+    pub currency: Option<String>,
+}
+
+pub fn format_pay(duration: chrono::Duration, rate: &Rate) -> String {
+    let pay = rate.amount * duration.num_minutes() as f64 / 60.0;
+    return match &rate.currency {
+        Some(currency) => format!("{:.2} {}", pay, currency),
+        None => format!("{:.2}", pay),
+    };
+}
+
+fn format_total(total: chrono::Duration, rate: &Option<Rate>) -> String {
+    return match rate {
+        Some(rate) => format!("Total: {} ({})", format_duration(total), format_pay(total, rate)),
+        None => format!("Total: {}", format_duration(total)),
+    };
+}
+
+fn sort_entries(entries: &mut Vec<TimesheetEntry>, sort: &SortOrder) {
+    entries.sort_by_key(|k| k.start);
+    if *sort == SortOrder::Desc {
+        entries.reverse();
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, PartialEq)]
+pub enum GroupBy {
+    Day,
+    Week,
+    Project,
+    None,
+}
+
+/// Group a shift is listed under: the first day of its day or week in the display timezone, or its project.
+#[derive(PartialEq)]
+enum GroupKey {
+    Date(NaiveDate),
+    Project(String),
+}
+
+fn group_key(entry: &TimesheetEntry, timezone: &Tz, group_by: &GroupBy) -> Option<GroupKey> {
+    let date = entry.start.with_timezone(timezone).date_naive();
+    return match group_by {
+        GroupBy::Day => Some(GroupKey::Date(date)),
+        // ISO weeks always start on Monday.
+        GroupBy::Week => Some(GroupKey::Date(start_of_week(date, WeekStart::Monday))),
+        GroupBy::Project => Some(GroupKey::Project(entry.project.clone())),
+        GroupBy::None => None,
+    };
+}
+
+/// Sorts the shifts by time, grouping by project keeps them in time order within each project.
+fn sort_for_grouping(entries: &mut Vec<TimesheetEntry>, sort: &SortOrder, group_by: &GroupBy) {
+    sort_entries(entries, sort);
+    if *group_by == GroupBy::Project {
+        entries.sort_by(|k, l| k.project.cmp(&l.project));
+    }
+}
+
+fn group_total_label(group_by: &GroupBy) -> Option<&'static str> {
+    return match group_by {
+        GroupBy::Day => Some("Day total"),
+        GroupBy::Week => Some("Week total"),
+        GroupBy::Project => Some("Project total"),
+        GroupBy::None => None,
+    };
+}
+
+/// Splits sorted entries into runs of shifts sharing the same group key.
+fn group_entries<'a>(entries: &'a [TimesheetEntry], timezone: &Tz, group_by: &GroupBy) -> Vec<&'a [TimesheetEntry]> {
+    return entries
+        .group_by(|k, l| group_key(k, timezone, group_by) == group_key(l, timezone, group_by))
+        .collect();
+}
+
+fn group_header(key: &GroupKey, group_by: &GroupBy) -> String {
+    return match (key, group_by) {
+        (GroupKey::Date(date), GroupBy::Week) => format!("Week {}-W{:02}", date.iso_week().year(), date.iso_week().week()),
+        (GroupKey::Date(date), _) => date.to_string(),
+        (GroupKey::Project(project), _) => project.clone(),
+    };
+}
+
+fn format_start(entry: &TimesheetEntry, timezone: &Tz, group_by: &GroupBy) -> String {
+    // Without a day header the date has to be part of the row.
+    let format = if *group_by == GroupBy::Day { "%H:%M" } else { "%Y-%m-%d %H:%M" };
+    return entry.start.with_timezone(timezone).format(format).to_string();
+}
+
+fn format_break(entry: &TimesheetEntry) -> String {
+    return match entry.break_duration().num_minutes() {
+        0 => "".to_string(),
+        minutes => f!("{minutes}m"),
+    };
+}
+
+/// Columns of the timesheet in the terminal, markdown and HTML tables.
+const TIMESHEET_HEADERS: [&str; 8] = ["Start", "End", "Description", "Project", "Subproject", "Break", "Duration", "ID"];
+
+/// Group headers and totals span all columns of the timesheet.
+const TIMESHEET_COLUMNS: usize = TIMESHEET_HEADERS.len();
+
+/// Position of the Duration column, the totals of the markdown table go there.
+const DURATION_COLUMN: usize = 6;
+
+/// The cells of a shift below `TIMESHEET_HEADERS`, without escaping or colors.
+fn shift_cells(entry: &TimesheetEntry, timezone: &Tz, group_by: &GroupBy) -> Vec<String> {
+    return vec![
+        format_start(entry, timezone, group_by),
+        match entry.end {
+            Some(end) => end.with_timezone(timezone).time().format("%H:%M").to_string(),
+            None => "in progress".to_string(),
+        },
+        entry.desc.clone(),
+        entry.project.clone(),
+        entry.subproject.clone(),
+        format_break(entry),
+        // Running shifts show the time so far, like the dimmed duration of the terminal table.
+        match entry.end {
+            Some(_) => format_duration(entry.net_duration()),
+            None => format_duration(elapsed(entry)),
+        },
+        entry.id.clone(),
+    ];
+}
+
+/// Limits the column widths so the table fits into `width` characters, long descriptions wrap inside their cell.
+fn fit_columns(table: &mut term_table::Table, width: usize) {
+    // Every column takes a border and two padding characters, plus the closing border.
+    let content_width = width.saturating_sub(TIMESHEET_COLUMNS * 3 + 1);
+    let name_width = (content_width / TIMESHEET_COLUMNS).max(8);
+    // Shift ids are 24 hex digits.
+    let fixed_widths = "HH:MM".len() + "in progress".len() + "999m".len() + "Duration".len() + 24;
+    let description_width = content_width.saturating_sub(fixed_widths + 2 * name_width).max(20);
+    table.set_max_column_widths(vec![(2, description_width), (3, name_width), (4, name_width)]);
+}
+
+pub fn render_timesheet(
+    entries: &mut Vec<TimesheetEntry>,
+    timezone: &Tz,
+    sort: &SortOrder,
+    rate: &Option<Rate>,
+    width: Option<usize>,
+    group_by: &GroupBy,
+    anomalies: &AnomalyRules,
+) -> String {
+    sort_for_grouping(entries, sort, group_by);
+    let grouped = group_entries(entries, timezone, group_by);
+
+    let mut table = term_table::Table::new();
+    table.max_column_width = 120;
+    table.style = term_table::TableStyle::extended();
+    if let Some(width) = width {
+        fit_columns(&mut table, width);
+    }
+
+    table.add_row(Row::new(TIMESHEET_HEADERS.iter().map(|header| TableCell::new(header)).collect::<Vec<_>>()));
+    let mut grand_total = chrono::Duration::zero();
+    for group in grouped {
+        let Some(first) = group.first() else { continue };
+        if let Some(key) = group_key(first, timezone, group_by) {
+            let header = match &key {
+                GroupKey::Date(date) if *group_by == GroupBy::Day && matches!(date.weekday(), Weekday::Sat | Weekday::Sun) => {
+                    group_header(&key, group_by).dimmed().to_string()
+                }
+                _ => group_header(&key, group_by),
+            };
+            table.add_row(Row::new(vec![TableCell::new_with_alignment(
+                header,
+                TIMESHEET_COLUMNS,
+                term_table::table_cell::Alignment::Center,
+            )]));
+        }
+        for entry in group {
+            let is_anomaly = !find_anomalies(entry, timezone, anomalies).is_empty();
+            let highlight = |text: String| if is_anomaly { text.red().to_string() } else { text };
+            table.add_row(Row::new(vec![
+                TableCell::new(highlight(format_start(entry, timezone, group_by))),
+                TableCell::new(match entry.end {
+                    Some(end) => highlight(end.with_timezone(timezone).time().format("%H:%M").to_string()),
+                    None => "in progress".to_string(),
+                }),
+                TableCell::new(&entry.desc),
+                TableCell::new(&entry.project),
+                TableCell::new(&entry.subproject),
+                TableCell::new(format_break(entry)),
+                // Worked time without breaks, the totals also leave out the overlaps marked by --dedup-overlaps.
+                TableCell::new(match entry.end {
+                    Some(_) => format_duration(entry.net_duration()),
+                    // Not part of the totals until punched out.
+                    None => format_duration(elapsed(entry)).dimmed().to_string(),
+                }),
+                TableCell::new(entry.id.dimmed().to_string()),
+            ]));
+        }
+
+        // Shifts crossing midnight count fully towards the day they started on.
+        let group_total = group
+            .iter()
+            .fold(chrono::Duration::zero(), |total, entry| total + entry.counted_duration());
+        grand_total = grand_total + group_total;
+        let Some(label) = group_total_label(group_by) else { continue };
+        table.add_row(Row::new(vec![TableCell::new_with_alignment(
+            format!("{}: {}", label, format_duration(group_total)),
+            TIMESHEET_COLUMNS,
+            term_table::table_cell::Alignment::Right,
+        )]));
+    }
+    return format!("{}\n{}\n", table.render(), format_total(grand_total, rate));
+}
+
+fn escape_markdown(text: &str) -> String {
+    return text.replace('|', "\\|").replace('\n', "<br>");
+}
+
+pub fn render_markdown(entries: &mut Vec<TimesheetEntry>, timezone: &Tz, sort: &SortOrder, rate: &Option<Rate>, group_by: &GroupBy) -> String {
+    sort_for_grouping(entries, sort, group_by);
+
+    let mut lines = vec![format!("| {} |", TIMESHEET_HEADERS.join(" | ")), format!("|{}", " --- |".repeat(TIMESHEET_COLUMNS))];
+    let mut grand_total = chrono::Duration::zero();
+    for group in group_entries(entries, timezone, group_by) {
+        let Some(first) = group.first() else { continue };
+        if let Some(key) = group_key(first, timezone, group_by) {
+            lines.push(format!("| **{}** |{}", escape_markdown(&group_header(&key, group_by)), " |".repeat(TIMESHEET_COLUMNS - 1)));
+        }
+        let mut group_total = chrono::Duration::zero();
+        for entry in group {
+            let cells: Vec<String> = shift_cells(entry, timezone, group_by).iter().map(|cell| escape_markdown(cell)).collect();
+            lines.push(format!("| {} |", cells.join(" | ")));
+            group_total = group_total + entry.counted_duration();
+        }
+        grand_total = grand_total + group_total;
+        let Some(label) = group_total_label(group_by) else { continue };
+        // Markdown has no cells spanning columns, the total goes below the durations.
+        let mut cells = vec!["".to_string(); TIMESHEET_COLUMNS];
+        cells[0] = format!("**{}**", label);
+        cells[DURATION_COLUMN] = format!("**{}**", format_duration(group_total));
+        lines.push(format!("| {} |", cells.join(" | ")));
+    }
+    return format!("{}\n\n{}\n", lines.join("\n"), format_total(grand_total, rate));
+}
+
+pub fn escape_html(text: &str) -> String {
+    return text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+}
+
+const HTML_STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+th { background: #eee; }
+tr.group td { background: #f5f5f5; font-weight: bold; text-align: center; }
+tr.subtotal td { text-align: right; font-style: italic; }";
+
+/// Table rows of the shifts with group headers and subtotals, together with the grand total.
+fn html_shift_rows(entries: &[TimesheetEntry], timezone: &Tz, group_by: &GroupBy) -> (Vec<String>, chrono::Duration) {
+    let headers: Vec<String> = TIMESHEET_HEADERS.iter().map(|header| format!("<th>{}</th>", header)).collect();
+    let mut rows = vec![format!("<tr>{}</tr>", headers.concat())];
+    let mut grand_total = chrono::Duration::zero();
+    for group in group_entries(entries, timezone, group_by) {
+        let Some(first) = group.first() else { continue };
+        if let Some(key) = group_key(first, timezone, group_by) {
+            rows.push(format!(
+                "<tr class=\"group\"><td colspan=\"{}\">{}</td></tr>",
+                TIMESHEET_COLUMNS,
+                escape_html(&group_header(&key, group_by))
+            ));
+        }
+        let mut group_total = chrono::Duration::zero();
+        for entry in group {
+            let cells: Vec<String> = shift_cells(entry, timezone, group_by).iter().map(|cell| format!("<td>{}</td>", escape_html(cell))).collect();
+            rows.push(format!("<tr>{}</tr>", cells.concat()));
+            group_total = group_total + entry.counted_duration();
+        }
+        grand_total = grand_total + group_total;
+        let Some(label) = group_total_label(group_by) else { continue };
+        rows.push(format!(
+            "<tr class=\"subtotal\"><td colspan=\"{}\">{}: {}</td></tr>",
+            TIMESHEET_COLUMNS,
+            label,
+            format_duration(group_total)
+        ));
+    }
+    return (rows, grand_total);
+}
+
+fn html_document(title: &str, body: &str) -> String {
+    return format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{}</title>
+<style>
+{}
+</style>
+</head>
+<body>
+{}
+</body>
+</html>
+",
+        escape_html(title),
+        HTML_STYLE,
+        body
+    );
+}
+
+pub fn render_html(entries: &mut Vec<TimesheetEntry>, timezone: &Tz, sort: &SortOrder, rate: &Option<Rate>, group_by: &GroupBy) -> String {
+    sort_for_grouping(entries, sort, group_by);
+    let (rows, grand_total) = html_shift_rows(entries, timezone, group_by);
+    let body = format!(
+        "<table>\n{}\n</table>\n<p><strong>{}</strong></p>",
+        rows.join("\n"),
+        escape_html(&format_total(grand_total, rate))
+    );
+    return html_document("Timesheet", &body);
+}
+
+/// Standalone report of a period: the shifts by day followed by the hours per project.
+pub fn render_html_report(entries: &mut Vec<TimesheetEntry>, timezone: &Tz, title: &str, rate: &Option<Rate>) -> String {
+    sort_entries(entries, &SortOrder::Asc);
+    let (rows, grand_total) = html_shift_rows(entries, timezone, &GroupBy::Day);
+
+    let mut project_rows = vec!["<tr><th>Project</th><th>Hours</th></tr>".to_string()];
+    for ((project, _), total) in project_totals(entries, &Summary::Project) {
+        project_rows.push(format!("<tr><td>{}</td><td>{}</td></tr>", escape_html(&project), format_duration(total)));
+    }
+
+    let body = format!(
+        "<h1>{}</h1>\n<table>\n{}\n</table>\n<h2>Projects</h2>\n<table>\n{}\n</table>\n<p><strong>{}</strong></p>",
+        escape_html(title),
+        rows.join("\n"),
+        project_rows.join("\n"),
+        escape_html(&format_total(grand_total, rate))
+    );
+    return html_document(title, &body);
+}
+
+#[derive(clap::ValueEnum, Clone, PartialEq)]
+pub enum Summary {
+    Project,
+    Subproject,
+}
+
+/// Worked time per project, or per project and subproject; the subproject is empty for `Summary::Project`.
+fn project_totals(entries: &[TimesheetEntry], summary: &Summary) -> BTreeMap<(String, String), chrono::Duration> {
+    let mut totals: BTreeMap<(String, String), chrono::Duration> = BTreeMap::new();
+    for entry in entries {
+        let subproject = match summary {
+            Summary::Project => "".to_string(),
+            Summary::Subproject => entry.subproject.clone(),
+        };
+        let total = totals.entry((entry.project.clone(), subproject)).or_insert(chrono::Duration::zero());
+        *total = *total + entry.counted_duration();
+    }
+    return totals;
+}
+
+pub fn render_summary(entries: &Vec<TimesheetEntry>, summary: &Summary, rate: &Option<Rate>) -> String {
+    let totals = project_totals(entries, summary);
+
+    let mut table = term_table::Table::new();
+    table.style = term_table::TableStyle::extended();
+
+    let mut header = vec![TableCell::new("Project")];
+    if *summary == Summary::Subproject {
+        header.push(TableCell::new("Subproject"));
+    }
+    header.push(TableCell::new("Hours"));
+    if rate.is_some() {
+        header.push(TableCell::new("Pay"));
+    }
+    table.add_row(Row::new(header));
+
+    let mut grand_total = chrono::Duration::zero();
+    for ((project, subproject), total) in &totals {
+        let mut row = vec![TableCell::new(project)];
+        if *summary == Summary::Subproject {
+            row.push(TableCell::new(subproject));
+        }
+        row.push(TableCell::new(format_duration(*total)));
+        if let Some(rate) = rate {
+            row.push(TableCell::new(format_pay(*total, rate)));
+        }
+        table.add_row(Row::new(row));
+        grand_total = grand_total + *total;
+    }
+    return format!("{}\n{}\n", table.render(), format_total(grand_total, rate));
+}
+
+/// Worked time per project, subproject, week and month in one table, each under its own heading.
+pub fn render_period_summary(entries: &Vec<TimesheetEntry>, timezone: &Tz, week_start: WeekStart, rate: &Option<Rate>) -> String {
+    let columns = if rate.is_some() { 3 } else { 2 };
+    let mut table = term_table::Table::new();
+    table.style = term_table::TableStyle::extended();
+
+    let mut sections: Vec<(&str, Vec<(String, chrono::Duration)>)> = vec![];
+    let projects = project_totals(entries, &Summary::Project).into_iter().map(|((project, _), total)| (project, total)).collect();
+    sections.push(("Projects", projects));
+    let subprojects = project_totals(entries, &Summary::Subproject)
+        .into_iter()
+        .filter(|((_, subproject), _)| !subproject.is_empty())
+        .map(|((project, subproject), total)| (f!("{project} / {subproject}"), total))
+        .collect();
+    sections.push(("Subprojects", subprojects));
+    let weeks = weekly_totals(entries, timezone, week_start)
+        .into_iter()
+        .map(|(week, total)| (format!("Week of {}", week.format("%Y-%m-%d")), total))
+        .collect();
+    sections.push(("Weeks", weeks));
+    let mut months: BTreeMap<NaiveDate, chrono::Duration> = BTreeMap::new();
+    for entry in entries {
+        let month = entry.start.with_timezone(timezone).date_naive().with_day(1).unwrap();
+        let total = months.entry(month).or_insert(chrono::Duration::zero());
+        *total = *total + entry.counted_duration();
+    }
+    sections.push(("Months", months.into_iter().map(|(month, total)| (month.format("%Y-%m").to_string(), total)).collect()));
+
+    for (title, totals) in sections {
+        if totals.is_empty() {
+            continue;
+        }
+        table.add_row(Row::new(vec![TableCell::new_with_alignment(
+            title.bold(),
+            columns,
+            term_table::table_cell::Alignment::Center,
+        )]));
+        for (name, total) in totals {
+            let mut row = vec![TableCell::new(name), TableCell::new(format_duration(total))];
+            if let Some(rate) = rate {
+                row.push(TableCell::new(format_pay(total, rate)));
+            }
+            table.add_row(Row::new(row));
+        }
+    }
+    let grand_total = entries.iter().fold(chrono::Duration::zero(), |total, entry| total + entry.counted_duration());
+    return format!("{}\n{}\n", table.render(), format_total(grand_total, rate));
+}
+
+pub fn render_stats(stats: &Stats) -> String {
+    let mut table = term_table::Table::new();
+    table.style = term_table::TableStyle::extended();
+    let (longest_date, longest_total) = stats.longest_day;
+    let rows = [
+        ("Days worked", stats.days_worked.to_string()),
+        ("Average punch in", stats.average_punch_in.format("%H:%M").to_string()),
+        ("Average punch out", stats.average_punch_out.map(|time| time.format("%H:%M").to_string()).unwrap_or("-".to_string())),
+        ("Average shift", format_duration(stats.average_shift)),
+        ("Longest day", format!("{} {} ({})", longest_date.format("%Y-%m-%d"), longest_date.weekday(), format_duration(longest_total))),
+    ];
+    for (name, value) in rows {
+        table.add_row(Row::new(vec![TableCell::new(name), TableCell::new(value)]));
+    }
+
+    let total = stats.weekdays.iter().fold(chrono::Duration::zero(), |total, (_, hours)| total + *hours);
+    table.add_row(Row::new(vec![TableCell::new_with_alignment(
+        "Hours per weekday".bold(),
+        2,
+        term_table::table_cell::Alignment::Center,
+    )]));
+    for (weekday, hours) in &stats.weekdays {
+        let share = match total.num_minutes() {
+            0 => 0.0,
+            minutes => 100.0 * hours.num_minutes() as f64 / minutes as f64,
+        };
+        table.add_row(Row::new(vec![TableCell::new(weekday), TableCell::new(format!("{} ({:.0}%)", format_duration(*hours), share))]));
+    }
+    return table.render();
+}
+
+/// Hours and amounts per project and subproject, followed by the total of each currency.
+pub fn render_earnings(earnings: &[Earning]) -> String {
+    let mut table = term_table::Table::new();
+    table.style = term_table::TableStyle::extended();
+    table.add_row(Row::new(vec![
+        TableCell::new("Project"),
+        TableCell::new("Subproject"),
+        TableCell::new("Hours"),
+        TableCell::new("Rate"),
+        TableCell::new("Amount"),
+    ]));
+    for earning in earnings {
+        let (rate, amount) = match (&earning.rate, earning.amount()) {
+            (Some(rate), Some(amount)) => {
+                let currency = rate.currency.as_deref().unwrap_or_default();
+                (format!("{:.2} {}", rate.rate, currency), format!("{:.2} {}", amount, currency))
+            }
+            _ => ("no rate".dimmed().to_string(), "".to_string()),
+        };
+        table.add_row(Row::new(vec![
+            TableCell::new(&earning.project),
+            TableCell::new(&earning.subproject),
+            TableCell::new(format_duration(earning.duration)),
+            TableCell::new(rate.trim_end()),
+            TableCell::new(amount.trim_end()),
+        ]));
+    }
+
+    let mut lines = vec![table.render()];
+    for (currency, total) in totals_by_currency(earnings) {
+        lines.push(format!("Total: {:.2} {}", total, currency).trim_end().to_string());
+    }
+    return format!("{}\n", lines.join("\n"));
+}
+
+/// Worked against contracted time per day with weekly subtotals, negative differences in red.
+pub fn render_overtime(days: &[OvertimeDay], week_start: WeekStart) -> String {
+    let mut table = term_table::Table::new();
+    table.style = term_table::TableStyle::extended();
+    table.add_row(Row::new(vec![
+        TableCell::new("Date"),
+        TableCell::new("Worked"),
+        TableCell::new("Contracted"),
+        TableCell::new("Difference"),
+        TableCell::new("Cumulative"),
+    ]));
+    let signed = |duration: chrono::Duration| {
+        let text = format_signed_duration(duration);
+        if duration < chrono::Duration::zero() { text.red().to_string() } else { text }
+    };
+
+    let weeks = days.group_by(|k, l| start_of_week(k.date, week_start) == start_of_week(l.date, week_start));
+    for week in weeks {
+        for day in week {
+            table.add_row(Row::new(vec![
+                TableCell::new(format!("{} {}", day.date.format("%Y-%m-%d"), day.date.weekday())),
+                TableCell::new(format_duration(day.worked)),
+                TableCell::new(format_duration(day.contracted)),
+                TableCell::new(signed(day.delta())),
+                TableCell::new(signed(day.cumulative)),
+            ]));
+        }
+        let worked = week.iter().fold(chrono::Duration::zero(), |total, day| total + day.worked);
+        let contracted = week.iter().fold(chrono::Duration::zero(), |total, day| total + day.contracted);
+        table.add_row(Row::new(vec![
+            TableCell::new(format!("Week of {}", start_of_week(week[0].date, week_start).format("%Y-%m-%d")).bold()),
+            TableCell::new(format_duration(worked)),
+            TableCell::new(format_duration(contracted)),
+            TableCell::new(signed(worked - contracted)),
+            TableCell::new(""),
+        ]));
+    }
+    let total = days.last().map(|day| day.cumulative).unwrap_or(chrono::Duration::zero());
+    return format!("{}\nOvertime: {}\n", table.render(), format_signed_duration(total));
+}
+
+/// One line per workday without shifts, followed by their count.
+pub fn render_gaps(gaps: &[NaiveDate]) -> String {
+    if gaps.is_empty() {
+        return "No workdays without shifts\n".to_string();
+    }
+    let mut lines: Vec<String> = gaps.iter().map(|date| format!("{} {}", date.format("%Y-%m-%d"), date.weekday()).red().to_string()).collect();
+    lines.push(format!("{} workdays without shifts", gaps.len()));
+    return lines.join("\n") + "\n";
+}
+
+/// Start, end and project of a shift on one line, for warnings and check results.
+pub fn describe_shift(entry: &TimesheetEntry, timezone: &Tz) -> String {
+    let end = entry.end.map(|end| end.with_timezone(timezone).format("%H:%M").to_string()).unwrap_or_default();
+    return format!("{} - {} {}", entry.start.with_timezone(timezone).format("%Y-%m-%d %H:%M"), end, shift_project(entry));
+}
+
+/// "Project / Subproject", or only the project without a subproject.
+pub fn shift_project(entry: &TimesheetEntry) -> String {
+    return match entry.subproject.as_str() {
+        "" => entry.project.clone(),
+        subproject => format!("{} / {}", entry.project, subproject),
+    };
+}
+
+/// Worked time of the running shift so far, breaks excluded.
+pub fn elapsed(shift: &TimesheetEntry) -> chrono::Duration {
+    return Utc::now() - shift.start - shift.break_duration();
+}
+
+/// Running shift as one line for status bars, e.g. "1:23 Acme / Backend".
+pub fn render_status_line(shift: Option<&TimesheetEntry>) -> String {
+    return match shift {
+        Some(shift) => format!("{} {}\n", format_duration(elapsed(shift)), shift_project(shift)),
+        None => "Clocked out\n".to_string(),
+    };
+}
+
+/// Running shift for a shell prompt, nothing when clocked out so the prompt stays short.
+pub fn render_prompt_status(shift: Option<&TimesheetEntry>) -> String {
+    let Some(shift) = shift else {
+        return "".to_string();
+    };
+    let minutes = elapsed(shift).num_minutes().max(0);
+    let time = match minutes / 60 {
+        0 => format!("{}m", minutes),
+        hours => format!("{}h{:02}m", hours, minutes % 60),
+    };
+    return format!("{} {}", format!("⏱ {}", time).yellow(), shift.project);
+}
+
+/// Output of a waybar custom module, the class lets the bar style clocked in and out differently.
+pub fn render_waybar_status(shift: Option<&TimesheetEntry>, timezone: &Tz) -> Result<String> {
+    let status = match shift {
+        Some(shift) => serde_json::json!({
+            "text": format!("{} {}", format_duration(elapsed(shift)), shift_project(shift)),
+            "tooltip": format!("Clocked in since {} on {}", shift.start.with_timezone(timezone).format("%H:%M"), shift_project(shift)),
+            "class": "clocked-in",
+        }),
+        None => serde_json::json!({"text": "", "tooltip": "Clocked out", "class": "clocked-out"}),
+    };
+    return Ok(serde_json::to_string(&status)? + "\n");
+}
+
+/// The overlapping pairs of `find_overlaps` with the time both shifts count.
+pub fn render_overlaps(entries: &[TimesheetEntry], overlaps: &[(usize, usize)], timezone: &Tz) -> String {
+    if overlaps.is_empty() {
+        return "No overlapping shifts\n".to_string();
+    }
+    let mut table = term_table::Table::new();
+    table.style = term_table::TableStyle::extended();
+    table.add_row(Row::new(vec![TableCell::new("Shift"), TableCell::new("Overlapping shift"), TableCell::new("Overlap")]));
+    for (first, second) in overlaps {
+        let (first, second) = (&entries[*first], &entries[*second]);
+        // Both are finished, find_overlaps skips running shifts.
+        let overlap = first.end.unwrap().min(second.end.unwrap()) - first.start.max(second.start);
+        table.add_row(Row::new(vec![
+            TableCell::new(describe_shift(first, timezone)),
+            TableCell::new(describe_shift(second, timezone)),
+            TableCell::new(format_duration(overlap).red()),
+        ]));
+    }
+    return format!("{}\n{} overlapping pairs\n", table.render(), overlaps.len());
+}
+
+/// Shifts breaking the `[anomalies]` rules with the reasons.
+pub fn render_anomalies(anomalies: &[(&TimesheetEntry, Vec<Anomaly>)], timezone: &Tz) -> String {
+    if anomalies.is_empty() {
+        return "No unusual shifts\n".to_string();
+    }
+    let mut table = term_table::Table::new();
+    table.style = term_table::TableStyle::extended();
+    table.add_row(Row::new(vec![TableCell::new("Shift"), TableCell::new("Anomalies")]));
+    for (entry, reasons) in anomalies {
+        let reasons: Vec<String> = reasons.iter().map(|reason| reason.to_string()).collect();
+        table.add_row(Row::new(vec![TableCell::new(describe_shift(entry, timezone)), TableCell::new(reasons.join(", ").red())]));
+    }
+    return format!("{}\n{} unusual shifts\n", table.render(), anomalies.len());
+}
+
+/// Sums the worked time per week, keyed by the first day of the week in the display timezone.
+pub fn weekly_totals(entries: &Vec<TimesheetEntry>, timezone: &Tz, week_start: WeekStart) -> BTreeMap<NaiveDate, chrono::Duration> {
+    let mut totals = BTreeMap::new();
+    for entry in entries {
+        let week = start_of_week(entry.start.with_timezone(timezone).date_naive(), week_start);
+        let total = totals.entry(week).or_insert(chrono::Duration::zero());
+        *total = *total + entry.counted_duration();
+    }
+    return totals;
+}
+
+pub fn render_tags(tags: &[Tag]) -> String {
+    let mut table = term_table::Table::new();
+    table.style = term_table::TableStyle::extended();
+    table.add_row(Row::new(vec![TableCell::new("Project"), TableCell::new("Subproject")]));
+    for tag in tags {
+        if tag.sub_items.is_empty() {
+            table.add_row(Row::new(vec![TableCell::new(&tag.name), TableCell::new("-")]));
+        }
+        for sub_item in &tag.sub_items {
+            table.add_row(Row::new(vec![TableCell::new(&tag.name), TableCell::new(&sub_item.name)]));
+        }
+    }
+    return table.render();
+}
+
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes();
+    return format!("{}:{:02}", minutes / 60, minutes % 60);
+}
+
+/// Duration with an explicit sign, for differences like overtime.
+fn format_signed_duration(duration: chrono::Duration) -> String {
+    let sign = if duration < chrono::Duration::zero() { "-" } else { "+" };
+    return format!("{}{}", sign, format_duration(duration.abs()));
+}
+
+pub fn render_csv(entries: &Vec<TimesheetEntry>, timezone: &Tz) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(&["start", "end", "duration", "description", "project", "subproject"])?;
+    for entry in entries {
+        writer.write_record(&[
+            entry.start.with_timezone(timezone).format("%Y-%m-%d %H:%M").to_string(),
+            entry.end.map(|end| end.with_timezone(timezone).format("%Y-%m-%d %H:%M").to_string()).unwrap_or_default(),
+            match entry.end {
+                Some(_) => format_duration(entry.net_duration()),
+                None => "".to_string(),
+            },
+            entry.desc.clone(),
+            entry.project.clone(),
+            entry.subproject.clone(),
+        ])?;
+    }
+    let csv = writer.into_inner().map_err(|err| anyhow::anyhow!("Failed to write csv: {}", err.error()))?;
+    return Ok(String::from_utf8(csv)?);
+}
+
+fn escape_ics_text(text: &str) -> String {
+    return text
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n");
+}
+
+fn fold_ics_line(line: String) -> String {
+    let mut folded = String::new();
+    let mut line_len = 0;
+    for c in line.chars() {
+        if line_len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            line_len = 1;
+        }
+        folded.push(c);
+        line_len += c.len_utf8();
+    }
+    return folded;
+}
+
+pub fn export_ics(entries: &[TimesheetEntry]) -> String {
+    let ics_timestamp = |timestamp: &DateTime<Utc>| timestamp.format("%Y%m%dT%H%M%SZ").to_string();
+    let now = ics_timestamp(&Utc::now());
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//connectteam-cli//timesheet//EN".to_string(),
+    ];
+    for entry in entries {
+        let project_slug: String = entry
+            .project
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let summary = if entry.subproject.is_empty() { entry.project.clone() } else { f!("{entry.project} / {entry.subproject}") };
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}-{}@connectteam-cli", entry.start.timestamp(), project_slug));
+        lines.push(f!("DTSTAMP:{now}"));
+        lines.push(format!("DTSTART:{}", ics_timestamp(&entry.start)));
+        // Shifts still in progress are exported without an end until they are punched out.
+        if let Some(end) = &entry.end {
+            lines.push(format!("DTEND:{}", ics_timestamp(end)));
+        }
+        lines.push(format!("SUMMARY:{}", escape_ics_text(&summary)));
+        if !entry.desc.is_empty() {
+            lines.push(format!("DESCRIPTION:{}", escape_ics_text(&entry.desc)));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    return lines.into_iter().map(fold_ics_line).collect::<Vec<_>>().join("\r\n") + "\r\n";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// `TimesheetEntry::test_shift` is only built for the tests of the library.
+    fn shift(desc: &str) -> TimesheetEntry {
+        return TimesheetEntry {
+            id: "".to_string(),
+            start: Utc.with_ymd_and_hms(2024, 5, 2, 9, 0, 0).unwrap(),
+            end: Some(Utc.with_ymd_and_hms(2024, 5, 2, 17, 0, 0).unwrap()),
+            desc: desc.to_string(),
+            notes: "".to_string(),
+            project: "Acme".to_string(),
+            subproject: "".to_string(),
+            breaks: vec![],
+            overlap: chrono::Duration::zero(),
+        };
+    }
+
+    #[test]
+    fn escapes_pipes_and_newlines_in_markdown() {
+        let mut entries = vec![shift("Fix a | b\nsecond line")];
+        let markdown = render_markdown(&mut entries, &chrono_tz::UTC, &SortOrder::Asc, &None, &GroupBy::None);
+        let lines: Vec<&str> = markdown.lines().collect();
+        // One row per shift, the description stays in its own cell.
+        assert_eq!(
+            lines,
+            vec![
+                "| Start | End | Description | Project | Subproject | Break | Duration | ID |",
+                "| --- | --- | --- | --- | --- | --- | --- | --- |",
+                "| 2024-05-02 09:00 | 17:00 | Fix a \\| b<br>second line | Acme |  |  | 8:00 |  |",
+                "",
+                "Total: 8:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        let mut entry = shift("Release 1.2, \"final\"");
+        entry.subproject = "Backend".to_string();
+        let entries = vec![entry];
+        let csv = render_csv(&entries, &chrono_tz::UTC).unwrap();
+        // Spreadsheets read the columns by their header, their order must not change.
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            vec![
+                "start,end,duration,description,project,subproject",
+                "2024-05-02 09:00,2024-05-02 17:00,8:00,\"Release 1.2, \"\"final\"\"\",Acme,Backend",
+            ]
+        );
+    }
+}
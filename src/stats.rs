@@ -0,0 +1,113 @@
+use chrono::{Datelike, NaiveDate, NaiveTime, Timelike, Weekday};
+use chrono_tz::Tz;
+use std::collections::BTreeMap;
+
+use crate::parse::TimesheetEntry;
+
+/// Work patterns of a period. Punch in and out times are per day, i.e. the first punch in and the last punch out.
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub days_worked: usize,
+    pub average_punch_in: NaiveTime,
+    /// `None` when no day has been punched out yet.
+    pub average_punch_out: Option<NaiveTime>,
+    /// Worked time of the finished shifts, breaks excluded.
+    pub average_shift: chrono::Duration,
+    pub longest_day: (NaiveDate, chrono::Duration),
+    /// Worked time per weekday, Monday first.
+    pub weekdays: Vec<(Weekday, chrono::Duration)>,
+}
+
+fn average_time(times: &[NaiveTime]) -> Option<NaiveTime> {
+    if times.is_empty() {
+        return None;
+    }
+    // Plain average of the seconds since midnight, shifts crossing midnight are rare enough not to wrap around.
+    let seconds = times.iter().map(|time| time.num_seconds_from_midnight() as u64).sum::<u64>() / times.len() as u64;
+    return NaiveTime::from_num_seconds_from_midnight_opt(seconds as u32, 0);
+}
+
+/// Statistics of the shifts in the display timezone, `None` without any shifts.
+pub fn compute_stats(entries: &[TimesheetEntry], timezone: &Tz) -> Option<Stats> {
+    let mut days: BTreeMap<NaiveDate, Vec<&TimesheetEntry>> = BTreeMap::new();
+    for entry in entries {
+        days.entry(entry.start.with_timezone(timezone).date_naive()).or_default().push(entry);
+    }
+    if days.is_empty() {
+        return None;
+    }
+
+    let mut punch_ins = vec![];
+    let mut punch_outs = vec![];
+    let mut day_totals = vec![];
+    for (date, day_entries) in &days {
+        punch_ins.push(day_entries.iter().map(|entry| entry.start).min().unwrap().with_timezone(timezone).time());
+        if let Some(end) = day_entries.iter().filter_map(|entry| entry.end).max() {
+            punch_outs.push(end.with_timezone(timezone).time());
+        }
+        let total = day_entries.iter().fold(chrono::Duration::zero(), |total, entry| total + entry.net_duration());
+        day_totals.push((*date, total));
+    }
+
+    let finished: Vec<&TimesheetEntry> = entries.iter().filter(|entry| entry.end.is_some()).collect();
+    let average_shift = match finished.len() {
+        0 => chrono::Duration::zero(),
+        count => finished.iter().fold(chrono::Duration::zero(), |total, entry| total + entry.net_duration()) / count as i32,
+    };
+
+    let mut weekdays: Vec<(Weekday, chrono::Duration)> =
+        (0..7).map(|day| (Weekday::try_from(day as u8).unwrap(), chrono::Duration::zero())).collect();
+    for (date, total) in &day_totals {
+        let weekday = &mut weekdays[date.weekday().num_days_from_monday() as usize];
+        weekday.1 = weekday.1 + *total;
+    }
+
+    // The first of several equally long days is reported.
+    let longest_day = day_totals.iter().fold(day_totals[0], |longest, day| if day.1 > longest.1 { *day } else { longest });
+    return Some(Stats {
+        days_worked: days.len(),
+        average_punch_in: average_time(&punch_ins).unwrap(),
+        average_punch_out: average_time(&punch_outs),
+        average_shift: average_shift,
+        longest_day: longest_day,
+        weekdays: weekdays,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn shift(day: u32, start: (u32, u32), end: Option<(u32, u32)>) -> TimesheetEntry {
+        return TimesheetEntry::test_shift(
+            Utc.with_ymd_and_hms(2023, 10, day, start.0, start.1, 0).unwrap(),
+            end.map(|(hour, minute)| Utc.with_ymd_and_hms(2023, 10, day, hour, minute, 0).unwrap()),
+        );
+    }
+
+    #[test]
+    fn computes_work_patterns() {
+        // 2023-10-02 is a Monday.
+        let entries = vec![
+            shift(2, (8, 0), Some((12, 0))),
+            shift(2, (13, 0), Some((17, 30))),
+            shift(3, (9, 0), Some((16, 0))),
+            shift(4, (8, 30), None),
+        ];
+        let stats = compute_stats(&entries, &chrono_tz::UTC).unwrap();
+        assert_eq!(stats.days_worked, 3);
+        assert_eq!(stats.average_punch_in, NaiveTime::from_hms_opt(8, 30, 0).unwrap());
+        assert_eq!(stats.average_punch_out, NaiveTime::from_hms_opt(16, 45, 0));
+        assert_eq!(stats.average_shift, chrono::Duration::minutes(310));
+        assert_eq!(stats.longest_day, (NaiveDate::from_ymd_opt(2023, 10, 2).unwrap(), chrono::Duration::minutes(510)));
+        assert_eq!(stats.weekdays[0], (Weekday::Mon, chrono::Duration::minutes(510)));
+        assert_eq!(stats.weekdays[2], (Weekday::Wed, chrono::Duration::zero()));
+        assert_eq!(stats.weekdays[6].0, Weekday::Sun);
+    }
+
+    #[test]
+    fn has_no_stats_without_shifts() {
+        assert_eq!(compute_stats(&[], &chrono_tz::UTC), None);
+    }
+}
@@ -0,0 +1,230 @@
+//! Local SQLite mirror of the fetched shifts, read instead of the API with `--offline` and kept up to date by `sync`.
+
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection};
+use std::path::{Path, PathBuf};
+
+use crate::error::ConnecteamError;
+use crate::parse::TimesheetEntry;
+
+/// Bumped when the tables change. The mirror only holds copies, so older layouts are dropped and synced again.
+const SCHEMA_VERSION: i64 = 4;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS shifts (
+        object_id INTEGER NOT NULL,
+        id TEXT NOT NULL,
+        start INTEGER NOT NULL,
+        end INTEGER,
+        desc TEXT NOT NULL,
+        notes TEXT NOT NULL,
+        project TEXT NOT NULL,
+        subproject TEXT NOT NULL,
+        breaks TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS shifts_start ON shifts (object_id, start);
+    CREATE TABLE IF NOT EXISTS syncs (
+        object_id INTEGER NOT NULL,
+        start INTEGER NOT NULL,
+        end INTEGER NOT NULL,
+        synced_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS marks (
+        object_id INTEGER PRIMARY KEY,
+        synced_until TEXT NOT NULL
+    );
+";
+
+pub fn default_store_path() -> PathBuf {
+    let mut store_file = home::home_dir().unwrap();
+    store_file.push(".local/share/connectteam/mirror.db");
+    return store_file;
+}
+
+/// Start of `date` in the timezone as a timestamp, days are mirrored as a whole.
+fn start_of_day(date: NaiveDate, timezone: &Tz) -> i64 {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap();
+    return match timezone.from_local_datetime(&midnight).earliest() {
+        Some(start) => start.timestamp(),
+        // Days that start in a DST gap begin an hour later.
+        None => timezone.from_utc_datetime(&midnight).timestamp(),
+    };
+}
+
+fn from_timestamp(timestamp: i64) -> DateTime<Utc> {
+    return DateTime::from_timestamp(timestamp, 0).unwrap_or_default();
+}
+
+/// Shifts of a range read from the mirror, with the time the range was last fetched from the API.
+pub struct MirroredEntries {
+    pub entries: Vec<TimesheetEntry>,
+    pub synced_at: Option<DateTime<Utc>>,
+}
+
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Store, ConnecteamError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        return Store::init(Connection::open(path)?);
+    }
+
+    fn init(conn: Connection) -> Result<Store, ConnecteamError> {
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            conn.execute_batch("DROP TABLE IF EXISTS shifts; DROP TABLE IF EXISTS syncs; DROP TABLE IF EXISTS marks;")?;
+        }
+        conn.execute_batch(SCHEMA)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        return Ok(Store { conn: conn });
+    }
+
+    /// Replaces the mirrored shifts of the punchclock on the days `start` to `end` with the ones just fetched.
+    pub fn replace_range(
+        &mut self,
+        object_id: u64,
+        start: NaiveDate,
+        end: NaiveDate,
+        timezone: &Tz,
+        entries: &[TimesheetEntry],
+    ) -> Result<(), ConnecteamError> {
+        let from = start_of_day(start, timezone);
+        let to = start_of_day(end.succ_opt().unwrap(), timezone);
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM shifts WHERE object_id = ?1 AND start >= ?2 AND start < ?3", params![object_id, from, to])?;
+        for entry in entries {
+            let breaks: Vec<(i64, i64)> = entry.breaks.iter().map(|(start, end)| (start.timestamp(), end.timestamp())).collect();
+            tx.execute(
+                "INSERT INTO shifts (object_id, id, start, end, desc, notes, project, subproject, breaks) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    object_id,
+                    entry.id,
+                    entry.start.timestamp(),
+                    entry.end.map(|end| end.timestamp()),
+                    entry.desc,
+                    entry.notes,
+                    entry.project,
+                    entry.subproject,
+                    serde_json::to_string(&breaks)?
+                ],
+            )?;
+        }
+        tx.execute(
+            "INSERT INTO syncs (object_id, start, end, synced_at) VALUES (?1, ?2, ?3, ?4)",
+            params![object_id, from, to, Utc::now().timestamp()],
+        )?;
+        return Ok(tx.commit()?);
+    }
+
+    /// Last day `sync` fetched for the punchclock, later syncs start shortly before it.
+    pub fn synced_until(&self, object_id: u64) -> Result<Option<NaiveDate>, ConnecteamError> {
+        let mut statement = self.conn.prepare("SELECT synced_until FROM marks WHERE object_id = ?1")?;
+        let mut rows = statement.query(params![object_id])?;
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+        let synced_until: String = row.get(0)?;
+        return Ok(NaiveDate::parse_from_str(&synced_until, "%Y-%m-%d").ok());
+    }
+
+    pub fn set_synced_until(&self, object_id: u64, date: NaiveDate) -> Result<(), ConnecteamError> {
+        self.conn.execute(
+            "INSERT INTO marks (object_id, synced_until) VALUES (?1, ?2) ON CONFLICT (object_id) DO UPDATE SET synced_until = ?2",
+            params![object_id, date.format("%Y-%m-%d").to_string()],
+        )?;
+        return Ok(());
+    }
+
+    /// Mirrored shifts of all punchclocks starting on the days `start` to `end`.
+    pub fn entries(&self, start: NaiveDate, end: NaiveDate, timezone: &Tz) -> Result<MirroredEntries, ConnecteamError> {
+        let from = start_of_day(start, timezone);
+        let to = start_of_day(end.succ_opt().unwrap(), timezone);
+        let mut statement = self
+            .conn
+            .prepare("SELECT id, start, end, desc, notes, project, subproject, breaks FROM shifts WHERE start >= ?1 AND start < ?2 ORDER BY start")?;
+        let rows = statement.query_map(params![from, to], |row| {
+            return Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, Option<i64>>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, String>(6)?,
+                row.get::<_, String>(7)?,
+            ));
+        })?;
+
+        let mut entries = vec![];
+        for row in rows {
+            let (id, start, end, desc, notes, project, subproject, breaks) = row?;
+            let breaks: Vec<(i64, i64)> = serde_json::from_str(&breaks)?;
+            entries.push(TimesheetEntry {
+                id: id,
+                start: from_timestamp(start),
+                end: end.map(from_timestamp),
+                desc: desc,
+                notes: notes,
+                project: project,
+                subproject: subproject,
+                breaks: breaks.into_iter().map(|(start, end)| (from_timestamp(start), from_timestamp(end))).collect(),
+                overlap: chrono::Duration::zero(),
+            });
+        }
+
+        let synced_at: Option<i64> = self.conn.query_row(
+            "SELECT MAX(synced_at) FROM syncs WHERE start < ?2 AND end > ?1",
+            params![from, to],
+            |row| row.get(0),
+        )?;
+        return Ok(MirroredEntries { entries: entries, synced_at: synced_at.map(from_timestamp) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(start: &str, project: &str) -> TimesheetEntry {
+        let start = DateTime::parse_from_rfc3339(start).unwrap().with_timezone(&Utc);
+        return TimesheetEntry::test_shift(start, Some(start + chrono::Duration::hours(8)))
+            .on_project(project, "")
+            .with_breaks(vec![(start + chrono::Duration::hours(4), start + chrono::Duration::minutes(270))]);
+    }
+
+    #[test]
+    fn replaces_mirrored_days() {
+        let mut store = Store::init(Connection::open_in_memory().unwrap()).unwrap();
+        let timezone: Tz = "Europe/Berlin".parse().unwrap();
+        let day = |day: u32| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+
+        store
+            .replace_range(1, day(1), day(2), &timezone, &[entry("2024-03-01T08:00:00+01:00", "Acme"), entry("2024-03-02T08:00:00+01:00", "Acme")])
+            .unwrap();
+        store.replace_range(1, day(2), day(2), &timezone, &[entry("2024-03-02T09:00:00+01:00", "Globex")]).unwrap();
+        store.replace_range(2, day(2), day(2), &timezone, &[entry("2024-03-02T18:00:00+01:00", "Initech")]).unwrap();
+
+        let mirrored = store.entries(day(1), day(3), &timezone).unwrap();
+        let projects: Vec<&str> = mirrored.entries.iter().map(|entry| entry.project.as_str()).collect();
+        assert_eq!(projects, vec!["Acme", "Globex", "Initech"]);
+        assert_eq!(mirrored.entries[0].break_duration(), chrono::Duration::minutes(30));
+        assert!(mirrored.synced_at.is_some());
+        assert!(store.entries(day(10), day(11), &timezone).unwrap().synced_at.is_none());
+    }
+
+    #[test]
+    fn tracks_sync_marks_per_object_id() {
+        let store = Store::init(Connection::open_in_memory().unwrap()).unwrap();
+        let day = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        assert_eq!(store.synced_until(1).unwrap(), None);
+        store.set_synced_until(1, day).unwrap();
+        store.set_synced_until(1, day.succ_opt().unwrap()).unwrap();
+        assert_eq!(store.synced_until(1).unwrap(), day.succ_opt());
+        assert_eq!(store.synced_until(2).unwrap(), None);
+    }
+}
@@ -0,0 +1,430 @@
+use chrono::{Datelike, NaiveDate, Utc, Weekday};
+use chrono_tz::Tz;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::dates::{end_of_month, start_of_month, start_of_week, WeekStart};
+use crate::parse::TimesheetEntry;
+use crate::render::{format_duration, shift_project};
+
+struct Day<'a> {
+    date: NaiveDate,
+    entries: Vec<&'a TimesheetEntry>,
+    expanded: bool,
+}
+
+impl Day<'_> {
+    fn total(&self) -> chrono::Duration {
+        return self
+            .entries
+            .iter()
+            .fold(chrono::Duration::zero(), |total, entry| total + entry.net_duration());
+    }
+}
+
+/// A visible line of the list, either a day header or one of its shifts.
+enum Row {
+    Day(usize),
+    Shift(usize, usize),
+}
+
+struct App<'a> {
+    days: Vec<Day<'a>>,
+    timezone: Tz,
+    state: ListState,
+}
+
+impl<'a> App<'a> {
+    fn new(entries: &'a [TimesheetEntry], timezone: &Tz) -> App<'a> {
+        let mut days: Vec<Day> = vec![];
+        for entry in entries {
+            let date = entry.start.with_timezone(timezone).date_naive();
+            match days.last_mut() {
+                Some(day) if day.date == date => day.entries.push(entry),
+                _ => days.push(Day { date: date, entries: vec![entry], expanded: true }),
+            }
+        }
+
+        let mut state = ListState::default();
+        if !days.is_empty() {
+            state.select(Some(0));
+        }
+        return App { days: days, timezone: *timezone, state: state };
+    }
+
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = vec![];
+        for (day_index, day) in self.days.iter().enumerate() {
+            rows.push(Row::Day(day_index));
+            if day.expanded {
+                rows.extend((0..day.entries.len()).map(|entry_index| Row::Shift(day_index, entry_index)));
+            }
+        }
+        return rows;
+    }
+
+    fn selected_day(&self) -> Option<usize> {
+        return match self.state.selected().and_then(|index| self.rows().into_iter().nth(index)) {
+            Some(Row::Day(day_index)) | Some(Row::Shift(day_index, _)) => Some(day_index),
+            None => None,
+        };
+    }
+
+    /// Moves the cursor to the header of `day_index`, so collapsing never leaves it on a hidden row.
+    fn select_day(&mut self, day_index: usize) {
+        let header = self
+            .rows()
+            .iter()
+            .position(|row| matches!(row, Row::Day(index) if *index == day_index));
+        self.state.select(header);
+    }
+
+    fn set_expanded(&mut self, expanded: bool) {
+        let Some(day_index) = self.selected_day() else { return };
+        self.days[day_index].expanded = expanded;
+        self.select_day(day_index);
+    }
+
+    fn toggle_expanded(&mut self) {
+        let Some(day_index) = self.selected_day() else { return };
+        self.set_expanded(!self.days[day_index].expanded);
+    }
+
+    fn set_all_expanded(&mut self, expanded: bool) {
+        let selected_day = self.selected_day();
+        for day in &mut self.days {
+            day.expanded = expanded;
+        }
+        if let Some(day_index) = selected_day {
+            self.select_day(day_index);
+        }
+    }
+
+    fn move_selection(&mut self, offset: i64) {
+        let len = self.rows().len() as i64;
+        if len == 0 {
+            return;
+        }
+        let current = self.state.selected().unwrap_or(0) as i64;
+        self.state.select(Some((current + offset).clamp(0, len - 1) as usize));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [list_area, footer_area] = Layout::vertical([Constraint::Min(1), Constraint::Length(3)]).areas(frame.area());
+
+        let items: Vec<ListItem> = self
+            .rows()
+            .iter()
+            .map(|row| match row {
+                Row::Day(day_index) => {
+                    let day = &self.days[*day_index];
+                    let marker = if day.expanded { "v" } else { ">" };
+                    let header = format!("{} {}  {}", marker, day.date.format("%Y-%m-%d %a"), format_duration(day.total()));
+                    ListItem::new(Line::styled(header, Style::default().add_modifier(Modifier::BOLD)))
+                }
+                Row::Shift(day_index, entry_index) => {
+                    let entry = self.days[*day_index].entries[*entry_index];
+                    let end = match entry.end {
+                        Some(end) => end.with_timezone(&self.timezone).format("%H:%M").to_string(),
+                        None => "in progress".to_string(),
+                    };
+                    ListItem::new(format!(
+                        "    {} - {}  {}  {} / {}  {}",
+                        entry.start.with_timezone(&self.timezone).format("%H:%M"),
+                        end,
+                        format_duration(entry.net_duration()),
+                        entry.project,
+                        entry.subproject,
+                        entry.desc
+                    ))
+                }
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Shifts"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.state);
+
+        let selected_total = match self.selected_day() {
+            Some(day_index) => self.days[day_index].total(),
+            None => chrono::Duration::zero(),
+        };
+        let grand_total = self.days.iter().fold(chrono::Duration::zero(), |total, day| total + day.total());
+        let footer = Paragraph::new(format!(
+            "Day: {}  Total: {}  |  up/down move, left/right collapse/expand, enter toggle, c/e all, q quit",
+            format_duration(selected_total),
+            format_duration(grand_total)
+        ))
+        .block(Block::default().borders(Borders::ALL));
+        frame.render_widget(footer, footer_area);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> std::io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else { continue };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+                KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+                KeyCode::PageDown => self.move_selection(10),
+                KeyCode::PageUp => self.move_selection(-10),
+                KeyCode::Left | KeyCode::Char('h') => self.set_expanded(false),
+                KeyCode::Right | KeyCode::Char('l') => self.set_expanded(true),
+                KeyCode::Enter | KeyCode::Char(' ') => self.toggle_expanded(),
+                KeyCode::Char('c') => self.set_all_expanded(false),
+                KeyCode::Char('e') => self.set_all_expanded(true),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Shows the entries in an interactive list grouped by day, in the order they are given.
+pub fn run(entries: &[TimesheetEntry], timezone: &Tz) -> std::io::Result<()> {
+    let mut app = App::new(entries, timezone);
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    return result;
+}
+
+/// What the calendar needs the API for, `browse` in main does it and hands the result back.
+pub enum CalendarAction {
+    Quit,
+    /// Shifts of the month starting on the date are needed.
+    LoadMonth(NaiveDate),
+    /// Punch in on the project typed by the user, matched loosely like `punch in --tag`.
+    PunchIn(String),
+    PunchOut,
+}
+
+/// Text typed in the footer, confirmed with enter.
+enum Input {
+    Filter(String),
+    Project(String),
+}
+
+/// Month calendar with the hours per day next to the shifts of the selected day.
+pub struct Calendar {
+    month: NaiveDate,
+    selected: NaiveDate,
+    entries: Vec<TimesheetEntry>,
+    timezone: Tz,
+    week_start: WeekStart,
+    /// Only shifts whose project, subproject or description contain it are shown and counted.
+    filter: String,
+    input: Option<Input>,
+    message: String,
+}
+
+impl Calendar {
+    pub fn new(selected: NaiveDate, timezone: &Tz, week_start: WeekStart) -> Calendar {
+        return Calendar {
+            month: start_of_month(selected),
+            selected: selected,
+            entries: vec![],
+            timezone: *timezone,
+            week_start: week_start,
+            filter: "".to_string(),
+            input: None,
+            message: "".to_string(),
+        };
+    }
+
+    pub fn month(&self) -> NaiveDate {
+        return self.month;
+    }
+
+    pub fn set_entries(&mut self, month: NaiveDate, entries: Vec<TimesheetEntry>) {
+        self.month = start_of_month(month);
+        self.entries = entries;
+        self.entries.sort_by_key(|k| k.start);
+    }
+
+    /// Shown in the footer until the next key press, e.g. the outcome of a punch.
+    pub fn set_message(&mut self, message: String) {
+        self.message = message;
+    }
+
+    fn day_entries(&self, date: NaiveDate) -> Vec<&TimesheetEntry> {
+        let filter = self.filter.to_lowercase();
+        return self
+            .entries
+            .iter()
+            .filter(|entry| entry.start.with_timezone(&self.timezone).date_naive() == date)
+            .filter(|entry| [&entry.project, &entry.subproject, &entry.desc].iter().any(|text| text.to_lowercase().contains(&filter)))
+            .collect();
+    }
+
+    fn day_total(&self, date: NaiveDate) -> chrono::Duration {
+        return self.day_entries(date).iter().fold(chrono::Duration::zero(), |total, entry| total + entry.net_duration());
+    }
+
+    fn calendar_lines(&self) -> Vec<Line<'static>> {
+        let today = Utc::now().with_timezone(&self.timezone).date_naive();
+        let first = start_of_week(self.month, self.week_start);
+        let header: Vec<Span> = (0..7)
+            .map(|offset| Span::styled(format!("{:<9}", (first + chrono::Duration::days(offset)).format("%a")), Style::default().add_modifier(Modifier::BOLD)))
+            .collect();
+        let mut lines = vec![Line::from(header)];
+        let mut week_start = first;
+        while week_start <= end_of_month(self.month) {
+            let cells: Vec<Span> = (0..7)
+                .map(|offset| {
+                    let date = week_start + chrono::Duration::days(offset);
+                    if date.month() != self.month.month() {
+                        return Span::raw(" ".repeat(9));
+                    }
+                    let total = self.day_total(date);
+                    let hours = if total > chrono::Duration::zero() { format_duration(total) } else { "".to_string() };
+                    let mut style = Style::default();
+                    if date == self.selected {
+                        style = style.add_modifier(Modifier::REVERSED);
+                    }
+                    if date == today {
+                        style = style.add_modifier(Modifier::UNDERLINED);
+                    }
+                    if matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+                        style = style.add_modifier(Modifier::DIM);
+                    }
+                    return Span::styled(format!("{:>2} {:>5} ", date.day(), hours), style);
+                })
+                .collect();
+            lines.push(Line::from(cells));
+            lines.push(Line::from(""));
+            week_start = week_start + chrono::Duration::days(7);
+        }
+        let month_total = self
+            .month
+            .iter_days()
+            .take_while(|date| *date <= end_of_month(self.month))
+            .fold(chrono::Duration::zero(), |total, date| total + self.day_total(date));
+        lines.push(Line::from(format!("Month: {}", format_duration(month_total))));
+        return lines;
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main_area, footer_area] = Layout::vertical([Constraint::Min(1), Constraint::Length(3)]).areas(frame.area());
+        let [calendar_area, day_area] = Layout::horizontal([Constraint::Length(65), Constraint::Min(20)]).areas(main_area);
+
+        let calendar = Paragraph::new(self.calendar_lines())
+            .block(Block::default().borders(Borders::ALL).title(self.month.format("%B %Y").to_string()));
+        frame.render_widget(calendar, calendar_area);
+
+        let shifts: Vec<ListItem> = self
+            .day_entries(self.selected)
+            .iter()
+            .map(|entry| {
+                let end = match entry.end {
+                    Some(end) => end.with_timezone(&self.timezone).format("%H:%M").to_string(),
+                    None => "in progress".to_string(),
+                };
+                return ListItem::new(vec![
+                    Line::from(format!(
+                        "{} - {}  {}  {}",
+                        entry.start.with_timezone(&self.timezone).format("%H:%M"),
+                        end,
+                        format_duration(entry.net_duration()),
+                        shift_project(entry)
+                    )),
+                    Line::styled(format!("  {}", entry.desc), Style::default().add_modifier(Modifier::DIM)),
+                ]);
+            })
+            .collect();
+        let title = format!("{}  {}", self.selected.format("%Y-%m-%d %a"), format_duration(self.day_total(self.selected)));
+        frame.render_widget(List::new(shifts).block(Block::default().borders(Borders::ALL).title(title)), day_area);
+
+        let footer = match &self.input {
+            Some(Input::Filter(text)) => format!("Filter: {}_  (enter apply, esc cancel)", text),
+            Some(Input::Project(text)) => format!("Punch in on project, e.g. acme/back: {}_  (enter punch in, esc cancel)", text),
+            None if !self.message.is_empty() => self.message.clone(),
+            None => {
+                let filter = if self.filter.is_empty() { "".to_string() } else { format!("Filter: {}  |  ", self.filter) };
+                format!("{}arrows move, n/p month, / filter, i punch in, o punch out, r reload, q quit", filter)
+            }
+        };
+        frame.render_widget(Paragraph::new(footer).block(Block::default().borders(Borders::ALL)), footer_area);
+    }
+
+    /// Moves the selection, leaving the month asks for the shifts of the new one.
+    fn select(&mut self, date: NaiveDate) -> Option<CalendarAction> {
+        self.selected = date;
+        if start_of_month(date) != self.month {
+            return Some(CalendarAction::LoadMonth(start_of_month(date)));
+        }
+        return None;
+    }
+
+    fn handle_input(&mut self, code: KeyCode) -> Option<CalendarAction> {
+        let input = self.input.take()?;
+        let (mut text, is_filter) = match input {
+            Input::Filter(text) => (text, true),
+            Input::Project(text) => (text, false),
+        };
+        match code {
+            KeyCode::Esc => return None,
+            KeyCode::Enter if is_filter => {
+                self.filter = text;
+                return None;
+            }
+            KeyCode::Enter => return Some(CalendarAction::PunchIn(text)),
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Char(c) => text.push(c),
+            _ => {}
+        }
+        self.input = Some(if is_filter { Input::Filter(text) } else { Input::Project(text) });
+        return None;
+    }
+
+    fn handle_key(&mut self, code: KeyCode) -> Option<CalendarAction> {
+        self.message.clear();
+        if self.input.is_some() {
+            return self.handle_input(code);
+        }
+        let day = chrono::Duration::days(1);
+        return match code {
+            KeyCode::Char('q') | KeyCode::Esc => Some(CalendarAction::Quit),
+            KeyCode::Left | KeyCode::Char('h') => self.select(self.selected - day),
+            KeyCode::Right | KeyCode::Char('l') => self.select(self.selected + day),
+            KeyCode::Up | KeyCode::Char('k') => self.select(self.selected - day * 7),
+            KeyCode::Down | KeyCode::Char('j') => self.select(self.selected + day * 7),
+            KeyCode::Char('n') | KeyCode::PageDown => self.select(start_of_month(end_of_month(self.month) + day)),
+            KeyCode::Char('p') | KeyCode::PageUp => self.select(start_of_month(self.month - day)),
+            KeyCode::Char('r') => Some(CalendarAction::LoadMonth(self.month)),
+            KeyCode::Char('/') => {
+                self.input = Some(Input::Filter(self.filter.clone()));
+                None
+            }
+            KeyCode::Char('i') => {
+                self.input = Some(Input::Project("".to_string()));
+                None
+            }
+            KeyCode::Char('o') => Some(CalendarAction::PunchOut),
+            _ => None,
+        };
+    }
+
+    /// Handles keys until one needs the API, the terminal stays in the TUI in between.
+    pub fn run_until_action(&mut self, terminal: &mut DefaultTerminal) -> std::io::Result<CalendarAction> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else { continue };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Some(action) = self.handle_key(key.code) {
+                return Ok(action);
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use chrono::{Datelike, NaiveDate};
+use chrono_tz::Tz;
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use std::collections::BTreeMap;
+
+use crate::dates::{start_of_week, WeekStart};
+use crate::parse::TimesheetEntry;
+
+const HOURS_COLUMN: u16 = 6;
+
+/// Spreadsheet reference of a cell in the hours column, `row` is zero-based.
+fn hours_cell(row: u32) -> String {
+    return format!("G{}", row + 1);
+}
+
+/// A row of a month sheet below the header, totals carry their label and formula.
+enum SheetRow<'a> {
+    Shift(NaiveDate, &'a TimesheetEntry),
+    Total(String, String),
+    Blank,
+}
+
+/// Rows of a month sheet: the shifts of each day followed by a day total, a week total after each week and the
+/// month total at the end.
+fn month_rows<'a>(days: &BTreeMap<NaiveDate, Vec<&'a TimesheetEntry>>) -> Vec<SheetRow<'a>> {
+    // The header takes the first row of the sheet.
+    let mut rows = vec![];
+    let next_row = |rows: &Vec<SheetRow>| rows.len() as u32 + 1;
+    let mut week_total_rows = vec![];
+    let mut day_total_rows = vec![];
+    let mut days = days.iter().peekable();
+    while let Some((date, day_entries)) = days.next() {
+        let first_row = next_row(&rows);
+        rows.extend(day_entries.iter().map(|entry| SheetRow::Shift(*date, *entry)));
+        let last_row = next_row(&rows) - 1;
+        day_total_rows.push(next_row(&rows));
+        rows.push(SheetRow::Total("Day total".to_string(), format!("=SUM({}:{})", hours_cell(first_row), hours_cell(last_row))));
+
+        // Weeks are closed before the next one starts and at the end of the month.
+        let week = start_of_week(*date, WeekStart::Monday);
+        let week_ends = match days.peek() {
+            Some((next_date, _)) => start_of_week(**next_date, WeekStart::Monday) != week,
+            None => true,
+        };
+        if week_ends {
+            let cells = day_total_rows.drain(..).map(hours_cell).collect::<Vec<_>>().join("+");
+            week_total_rows.push(next_row(&rows));
+            rows.push(SheetRow::Total(format!("Week {}-W{:02} total", week.iso_week().year(), week.iso_week().week()), format!("={}", cells)));
+            rows.push(SheetRow::Blank);
+        }
+    }
+
+    let cells = week_total_rows.into_iter().map(hours_cell).collect::<Vec<_>>().join("+");
+    rows.push(SheetRow::Total("Month total".to_string(), format!("={}", cells)));
+    return rows;
+}
+
+/// Workbook with one sheet per month, totals are formulas so edited hours add up again.
+pub fn export_xlsx(entries: &[TimesheetEntry], timezone: &Tz) -> Result<Vec<u8>, XlsxError> {
+    let mut months: BTreeMap<NaiveDate, BTreeMap<NaiveDate, Vec<&TimesheetEntry>>> = BTreeMap::new();
+    for entry in entries {
+        let date = entry.start.with_timezone(timezone).date_naive();
+        let month = date.with_day(1).unwrap();
+        months.entry(month).or_default().entry(date).or_default().push(entry);
+    }
+
+    let bold = Format::new().set_bold();
+    let hours = Format::new().set_num_format("0.00");
+    let bold_hours = Format::new().set_bold().set_num_format("0.00");
+
+    let mut workbook = Workbook::new();
+    for (month, days) in &months {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(month.format("%Y-%m").to_string())?;
+        for (column, title) in ["Date", "Start", "End", "Project", "Subproject", "Description", "Hours"].iter().enumerate() {
+            worksheet.write_string_with_format(0, column as u16, *title, &bold)?;
+        }
+
+        for (index, sheet_row) in month_rows(days).iter().enumerate() {
+            let row = index as u32 + 1;
+            match sheet_row {
+                SheetRow::Shift(date, entry) => {
+                    worksheet.write_string(row, 0, date.format("%Y-%m-%d").to_string())?;
+                    worksheet.write_string(row, 1, entry.start.with_timezone(timezone).format("%H:%M").to_string())?;
+                    if let Some(end) = entry.end {
+                        worksheet.write_string(row, 2, end.with_timezone(timezone).format("%H:%M").to_string())?;
+                    }
+                    worksheet.write_string(row, 3, &entry.project)?;
+                    worksheet.write_string(row, 4, &entry.subproject)?;
+                    worksheet.write_string(row, 5, &entry.desc)?;
+                    worksheet.write_number_with_format(row, HOURS_COLUMN, entry.net_duration().num_minutes() as f64 / 60.0, &hours)?;
+                }
+                SheetRow::Total(label, formula) => {
+                    worksheet.write_string_with_format(row, 5, label, &bold)?;
+                    worksheet.write_formula_with_format(row, HOURS_COLUMN, formula.as_str(), &bold_hours)?;
+                }
+                SheetRow::Blank => {}
+            }
+        }
+        worksheet.autofit();
+    }
+    return workbook.save_to_buffer();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    /// `TimesheetEntry::test_shift` is only built for the tests of the library.
+    fn shift(day: u32, start_hour: u32) -> TimesheetEntry {
+        return TimesheetEntry {
+            id: "".to_string(),
+            start: Utc.with_ymd_and_hms(2024, 5, day, start_hour, 0, 0).unwrap(),
+            end: Some(Utc.with_ymd_and_hms(2024, 5, day, start_hour + 2, 0, 0).unwrap()),
+            desc: "".to_string(),
+            notes: "".to_string(),
+            project: "Acme".to_string(),
+            subproject: "".to_string(),
+            breaks: vec![],
+            overlap: chrono::Duration::zero(),
+        };
+    }
+
+    #[test]
+    fn sums_days_weeks_and_month() {
+        // Thursday and Friday of 2024-W18, Monday of 2024-W19.
+        let entries = vec![shift(2, 9), shift(2, 13), shift(3, 9), shift(6, 9)];
+        let mut days: BTreeMap<NaiveDate, Vec<&TimesheetEntry>> = BTreeMap::new();
+        for entry in &entries {
+            days.entry(entry.start.date_naive()).or_default().push(entry);
+        }
+
+        // Sheet row numbers of the totals, the header is row 1.
+        let totals: Vec<(usize, String, String)> = month_rows(&days)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, row)| match row {
+                SheetRow::Total(label, formula) => Some((index + 2, label, formula)),
+                _ => None,
+            })
+            .collect();
+        let total = |row: usize, label: &str, formula: &str| (row, label.to_string(), formula.to_string());
+        assert_eq!(
+            totals,
+            vec![
+                total(4, "Day total", "=SUM(G2:G3)"),
+                total(6, "Day total", "=SUM(G5:G5)"),
+                total(7, "Week 2024-W18 total", "=G4+G6"),
+                total(10, "Day total", "=SUM(G9:G9)"),
+                total(11, "Week 2024-W19 total", "=G10"),
+                total(13, "Month total", "=G7+G11"),
+            ]
+        );
+    }
+}